@@ -1,9 +1,9 @@
 use std::fmt::Display;
 
 use ac3::{
-    ac3::{ConstraintProvider, DomainType, IdentifierType},
+    ac3::{ConstraintProvider, DomainType, IdentifierType, arcs},
     backtrack,
-    variable_provider::{Variable, VariableID, VariableProvider},
+    variable_provider::{Variable, VariableProvider},
 };
 use rand::prelude::SmallRng;
 use rand_seeder::Seeder;
@@ -70,7 +70,7 @@ impl Coordinate {
         let y_adjacent =
             (self.y > 0 && self.y - 1 == other.y) || (self.y < usize::MAX && self.y + 1 == other.y);
 
-        if !(x_adjacent ^ y_adjacent) {
+        if !((x_adjacent && self.y == other.y) || (y_adjacent && self.x == other.x)) {
             None
         } else if self.x < other.x {
             Some(Direction::Right)
@@ -157,33 +157,6 @@ pub(crate) struct TileSet {
     pub relations: Vec<[Vec<bool>; 4]>,
 }
 
-/// Builds a list of bidirectional arcs between coordinates in a 2d grid.
-#[must_use]
-fn build_arcs(
-    variables: &VariableProvider<Tile, Coordinate>,
-    x_lim: usize,
-    y_lim: usize,
-) -> Vec<(VariableID, VariableID)> {
-    let mut arcs = vec![];
-    for x in 0..x_lim {
-        for y in 0..y_lim {
-            let base = variables.find_id(Coordinate::new(x, y)).unwrap();
-            if x < x_lim - 1 {
-                let other = variables.find_id(Coordinate::new(x + 1, y)).unwrap();
-                arcs.push((base, other));
-                arcs.push((other, base));
-            }
-            if y < y_lim - 1 {
-                let other = variables.find_id(Coordinate::new(x, y + 1)).unwrap();
-                arcs.push((base, other));
-                arcs.push((other, base));
-            }
-        }
-    }
-
-    arcs
-}
-
 fn new_relation() -> [Vec<bool>; 4] {
     let tile_count = Tile::len();
     let relations = vec![false; tile_count];
@@ -332,6 +305,25 @@ impl ConstraintProvider<Tile, Coordinate> for TileSet {
             false
         }
     }
+
+    fn constrains(&self, a: &Variable<Tile, Coordinate>, b: &Variable<Tile, Coordinate>) -> bool {
+        a.identifier.is_adjacent(&b.identifier).is_some()
+    }
+
+    fn neighbours(&self, a: &Variable<Tile, Coordinate>) -> Option<Vec<Coordinate>> {
+        let Coordinate { x, y } = a.identifier;
+        Some(
+            [
+                x.checked_sub(1).map(|x| Coordinate::new(x, y)),
+                x.checked_add(1).map(|x| Coordinate::new(x, y)),
+                y.checked_sub(1).map(|y| Coordinate::new(x, y)),
+                y.checked_add(1).map(|y| Coordinate::new(x, y)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        )
+    }
 }
 
 /// Inserts some data into the map to pre-seed some interesting shapes.
@@ -611,9 +603,9 @@ fn main() {
         ],
     );
     //
-    let mut arcs = build_arcs(&variables, x_lim, y_lim);
+    let mut arcs = arcs(&variables, &tiles);
 
-    let happy = backtrack::reduce(&mut variables, &mut arcs, &tiles, &mut rng);
+    let (happy, statistics) = backtrack::reduce(&mut variables, &mut arcs, &tiles, &mut rng);
     match happy {
         backtrack::Solution::Consistent => {
             print_domains(&variables, y_lim, x_lim);
@@ -621,7 +613,11 @@ fn main() {
         backtrack::Solution::NoSolution => {
             println!("No solution found");
         }
+        backtrack::Solution::LimitReached(limit) => {
+            println!("Gave up searching: {limit:?}");
+        }
     };
+    println!("{statistics:#?}");
 }
 
 fn print_domains(variables: &VariableProvider<Tile, Coordinate>, y_lim: usize, x_lim: usize) {
@@ -672,6 +668,7 @@ mod test {
         let c1_1 = Coordinate::new(1, 1);
         let c0_1 = Coordinate::new(0, 1);
         assert_eq!(c0_0.is_adjacent(&c1_1), None);
+        assert_eq!(c0_0.is_adjacent(&Coordinate::new(2, 1)), None);
         assert_eq!(c0_0.is_adjacent(&c0_1), Some(Direction::Up));
         assert_eq!(c0_1.is_adjacent(&c0_0), Some(Direction::Down));
         assert_eq!(c0_1.is_adjacent(&c1_1), Some(Direction::Right));
@@ -775,7 +772,7 @@ mod test {
     }
 
     #[test]
-    fn build_arcs() {
+    fn arcs_join_adjacent_coordinates() {
         let mut vars = VariableProvider::<Tile, Coordinate>::default();
         let x_lim = 3;
         let y_lim = 2;
@@ -786,7 +783,7 @@ mod test {
             }
         }
 
-        let arcs = super::build_arcs(&vars, x_lim, y_lim);
+        let arcs = ac3::ac3::arcs(&vars, &TileSet::default());
         assert_eq!(arcs.len(), 14);
     }
 }
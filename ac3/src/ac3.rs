@@ -1,35 +1,56 @@
 use std::{
-    collections::VecDeque,
+    cell::Cell,
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{Debug, Display},
     hash::Hash,
 };
 
+use crate::observer::SearchObserver;
+use crate::statistics::Statistics;
 use crate::variable_provider::{Variable, VariableID, VariableProvider};
 
 pub trait DomainType: Clone + PartialEq + Copy + Debug + Display {}
 pub trait IdentifierType: Eq + PartialEq + Hash + Ord + Copy + Clone + Display + Debug {}
 
-/// Iterate `x`'s remaining domain values, and keep any that satisfy available constraints.
-fn retain<D, K, CP>(x: &Variable<D, K>, y: &Variable<D, K>, constraints: &CP) -> bool
+/// Iterate `x`'s remaining domain values, and keep any that satisfy available constraints, unless
+/// the provider has a faster way to revise the arc. Returns the values that were removed.
+pub(crate) fn retain<D, K, CP>(x: &Variable<D, K>, y: &Variable<D, K>, constraints: &CP) -> Vec<D>
 where
     D: DomainType,
     CP: ConstraintProvider<D, K>,
     K: IdentifierType,
 {
-    let mut revised = false;
+    if let Some(removed) = constraints.revise(x, y) {
+        return removed;
+    }
+
+    retain_by(x, y, |x_value, y_value| {
+        constraints.check(x, x_value, y, y_value)
+    })
+}
+
+/// Keep the values of `x` that some value of `y` satisfies `check` with. Returns the values
+/// that were removed.
+pub(crate) fn retain_by<D, K, F>(x: &Variable<D, K>, y: &Variable<D, K>, check: F) -> Vec<D>
+where
+    D: DomainType,
+    K: IdentifierType,
+    F: Fn(&D, &D) -> bool,
+{
+    let mut removed = vec![];
 
     x.retain(|x_value| {
         let satisfies = y
             .possible_values()
             .iter()
-            .any(|y_value| constraints.check(x, x_value, y, y_value));
+            .any(|y_value| check(x_value, y_value));
         if !satisfies {
-            revised = true;
+            removed.push(*x_value);
         }
         satisfies
     });
 
-    revised
+    removed
 }
 
 /// Allows users to provide a mechanism to validate binary constraints between
@@ -42,6 +63,123 @@ where
     /// Determine if variable a has a valid relationship with b based on their
     /// identity and value.
     fn check(&self, a: &Variable<D, K>, a_value: &D, b: &Variable<D, K>, b_value: &D) -> bool;
+
+    /// Whether any constraint relates `a` to `b`, making `(a, b)` an arc. This is the scope
+    /// [`arcs`] derives the arc list from, so it should agree with [`ConstraintProvider::check`].
+    /// Defaults to relating every pair of variables, which is only correct if
+    /// [`ConstraintProvider::check`] accepts every pair of values of unrelated variables.
+    /// Providers whose check rejects those, e.g. by returning `false` for variables that are not
+    /// adjacent, must override this, or every pair of variables is constrained.
+    fn constrains(&self, _a: &Variable<D, K>, _b: &Variable<D, K>) -> bool {
+        true
+    }
+
+    /// Remove every value of `a` that no value of `b` supports, returning the removed values.
+    /// Providers that know the shape of their relation can do this faster than checking every
+    /// pair of values, e.g. by comparing bounds. Returning `None` (the default) leaves `a` alone
+    /// and falls back to the pairwise scan with [`ConstraintProvider::check`].
+    fn revise(&self, _a: &Variable<D, K>, _b: &Variable<D, K>) -> Option<Vec<D>> {
+        None
+    }
+
+    /// Identifiers of the variables `a` may be related to, so [`arcs`] can look them up instead
+    /// of trying every other variable. Listing extra identifiers is harmless, since
+    /// [`ConstraintProvider::constrains`] still decides each arc. Returning `None` (the default)
+    /// makes [`arcs`] try every variable.
+    fn neighbours(&self, _a: &Variable<D, K>) -> Option<Vec<K>> {
+        None
+    }
+
+    /// Like [`ConstraintProvider::neighbours`], for providers that know the variables `a` may be
+    /// related to by [`VariableID`] rather than by identifier. Takes precedence over it when
+    /// listed.
+    fn neighbour_ids(&self, _a: &Variable<D, K>) -> Option<Vec<VariableID>> {
+        None
+    }
+}
+
+/// Every arc `constraints` declares between `variables`, see [`ConstraintProvider::constrains`].
+/// Deriving arcs this way keeps them in sync with the constraints, instead of building the list
+/// by hand.
+///
+/// Only the [`ConstraintProvider::neighbour_ids`] or [`ConstraintProvider::neighbours`] of each
+/// variable are tried when the provider lists them. Otherwise every pair of variables is, which
+/// takes time quadratic in their number.
+#[must_use]
+pub fn arcs<K, D, CP>(
+    variables: &VariableProvider<D, K>,
+    constraints: &CP,
+) -> Vec<(VariableID, VariableID)>
+where
+    D: DomainType,
+    K: IdentifierType,
+    CP: ConstraintProvider<D, K>,
+{
+    let ids = variables
+        .iter()
+        .map(|v| (v.identifier, v.index))
+        .collect::<HashMap<_, _>>();
+    let mut arcs = vec![];
+    for a in variables.iter() {
+        let candidates = if let Some(mut listed) = constraints.neighbour_ids(a) {
+            let mut seen = HashSet::new();
+            listed.retain(|id| seen.insert(*id));
+            listed
+                .into_iter()
+                .filter_map(|id| variables.get_var(id))
+                .collect()
+        } else if let Some(mut neighbours) = constraints.neighbours(a) {
+            neighbours.sort_unstable();
+            neighbours.dedup();
+            neighbours
+                .iter()
+                .filter_map(|id| variables.get_var(*ids.get(id)?))
+                .collect()
+        } else {
+            variables.iter().collect::<Vec<_>>()
+        };
+        arcs.extend(
+            candidates
+                .into_iter()
+                .filter(|b| a.index != b.index && constraints.constrains(a, b))
+                .map(|b| (a.index, b.index)),
+        );
+    }
+    arcs
+}
+
+/// Wraps a [`ConstraintProvider`] to count how often it is consulted.
+struct Counting<'a, CP> {
+    inner: &'a CP,
+    checks: Cell<u64>,
+}
+
+impl<D, K, CP> ConstraintProvider<D, K> for Counting<'_, CP>
+where
+    D: DomainType,
+    K: IdentifierType,
+    CP: ConstraintProvider<D, K>,
+{
+    fn check(&self, a: &Variable<D, K>, a_value: &D, b: &Variable<D, K>, b_value: &D) -> bool {
+        self.checks.set(self.checks.get() + 1);
+        self.inner.check(a, a_value, b, b_value)
+    }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        self.inner.constrains(a, b)
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        self.inner.revise(a, b)
+    }
+
+    fn neighbours(&self, a: &Variable<D, K>) -> Option<Vec<K>> {
+        self.inner.neighbours(a)
+    }
+
+    fn neighbour_ids(&self, a: &Variable<D, K>) -> Option<Vec<VariableID>> {
+        self.inner.neighbour_ids(a)
+    }
 }
 
 /// Utility type for making boxes a little simpler. Probably should be removed
@@ -57,29 +195,40 @@ where
     Box::new(f)
 }
 
+/// A [`Constraint`] that can be shared between threads, so a [`HashMap`] of them can be searched
+/// by [`crate::portfolio::portfolio`].
+pub type SyncConstraint<D> = Box<dyn Fn(&D, &D) -> bool + Send + Sync>;
+
+/// Utility function for making [`SyncConstraint`]s.
+pub fn new_sync_constraint<D>(
+    f: impl Fn(&D, &D) -> bool + Send + Sync + 'static,
+) -> SyncConstraint<D>
+where
+    D: DomainType,
+{
+    Box::new(f)
+}
+
 /// Removes invalid domain values from a given variable `x`, by verifying
-/// constraints in relation to `y`.
+/// constraints in relation to `y`. Returns the values that were removed.
 fn revise<K, D, CP>(
     variables: &VariableProvider<D, K>,
     constraints: &CP,
     x: VariableID,
     y: VariableID,
-) -> bool
+) -> Vec<D>
 where
     D: DomainType,
     CP: ConstraintProvider<D, K>,
     K: IdentifierType,
 {
-    let mut revised = false;
-
     if let Some(x_var) = variables.get_var(x)
         && let Some(y_var) = variables.get_var(y)
-        && retain(x_var, y_var, constraints)
     {
-        revised = true;
+        retain(x_var, y_var, constraints)
+    } else {
+        vec![]
     }
-
-    revised
 }
 
 /// Entrypoint for a very basic version of [AC-3](https://en.wikipedia.org/wiki/AC-3_algorithm).
@@ -90,6 +239,11 @@ where
 /// - a [`ConstraintProvider`] that provides the rules for validating the relationships between variables
 /// - a collection of pairs of variables that are used to indicate which variables are related to each other.
 ///
+/// Propagation stops as soon as a variable runs out of possible values, since nothing can satisfy
+/// the constraints from there.
+///
+/// Returns [`Statistics`] with the propagation counters filled in.
+///
 /// # Example
 ///
 /// See [`test::validate_ac3`].
@@ -98,20 +252,98 @@ pub fn ac3<K, D, CP>(
     variables: &mut VariableProvider<D, K>,
     arcs: &[(VariableID, VariableID)],
     constraints: &CP,
-) where
+) -> Statistics
+where
     D: DomainType,
     K: IdentifierType,
     CP: ConstraintProvider<D, K>,
 {
-    let mut queue = arcs.iter().copied().collect::<VecDeque<_>>();
+    ac3_observed(variables, arcs, constraints, &mut ())
+}
+
+/// Same as [`ac3`], reporting every domain reduction and wipeout to `observer`.
+pub fn ac3_observed<K, D, CP, O>(
+    variables: &mut VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    observer: &mut O,
+) -> Statistics
+where
+    D: DomainType,
+    K: IdentifierType,
+    CP: ConstraintProvider<D, K>,
+    O: SearchObserver<D, K> + ?Sized,
+{
+    let mut statistics = Statistics::default();
+    let queue = arcs.iter().copied().collect();
+    let variables = &*variables;
+    let wipeout = propagate(
+        variables,
+        arcs,
+        constraints,
+        queue,
+        &mut statistics,
+        |x, y, removed| {
+            if let Some(neighbour) = variables.get_var(y) {
+                observer.on_revision(x, removed, y, &neighbour.possible_values());
+            }
+            observer.on_reduction(x, removed);
+        },
+        |_| true,
+    );
+    if let Some((x, _)) = wipeout {
+        observer.on_wipeout(x);
+    }
+    statistics
+}
+
+/// The AC-3 loop behind [`ac3`], starting from an arbitrary `queue` of arcs. Calls `on_reduction`
+/// with `x`, `y` and the removed values after each revision of an arc `(x, y)` that removed
+/// something, and stops as soon as a domain is emptied, returning the arc that emptied it. Arcs
+/// affected by a reduction are only queued again if `requeue` accepts them.
+pub(crate) fn propagate<K, D, CP, F, G>(
+    variables: &VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    mut queue: VecDeque<(VariableID, VariableID)>,
+    statistics: &mut Statistics,
+    mut on_reduction: F,
+    requeue: G,
+) -> Option<(VariableID, VariableID)>
+where
+    D: DomainType,
+    K: IdentifierType,
+    CP: ConstraintProvider<D, K>,
+    F: FnMut(VariableID, VariableID, &[D]),
+    G: Fn(&(VariableID, VariableID)) -> bool,
+{
+    let constraints = Counting {
+        inner: constraints,
+        checks: Cell::new(0),
+    };
+    let mut wipeout = None;
 
     while let Some((x, y)) = queue.pop_front() {
-        let revised = revise(variables, constraints, x, y);
+        statistics.revisions += 1;
+        let removed = revise(variables, &constraints, x, y);
 
-        if revised {
-            queue.extend(arcs.iter().filter(|(_, b)| b.eq(&x)));
+        if !removed.is_empty() {
+            statistics.reductions += 1;
+            on_reduction(x, y, &removed);
+            if variables.get_var(x).is_some_and(|v| !v.is_consistent()) {
+                wipeout = Some((x, y));
+                break;
+            }
+            queue.extend(
+                arcs.iter()
+                    .filter(|(_, b)| b.eq(&x))
+                    .filter(|arc| requeue(arc)),
+            );
         }
     }
+
+    statistics.checks += constraints.checks.get();
+    wipeout
 }
 
 #[cfg(test)]
@@ -134,6 +366,115 @@ mod test {
         assert!(variables.get_var(b).unwrap().possible_values().eq(&vec!(1)));
     }
 
+    #[test]
+    fn arcs_follow_the_constraints() {
+        let variables = VariableProvider::from([('a', vec![1]), ('b', vec![1]), ('c', vec![1])]);
+        let [a, b, c] = ['a', 'b', 'c'].map(|id| variables.find_id(id).unwrap());
+        let constraints = HashMap::from([
+            ((a, b), new_constraint(|x: &i32, y: &i32| x == y)),
+            ((c, a), new_constraint(|x: &i32, y: &i32| x == y)),
+        ]);
+        let mut derived = arcs(&variables, &constraints);
+        derived.sort_by_key(|(x, y)| {
+            (
+                variables.get_var(*x).unwrap().identifier,
+                variables.get_var(*y).unwrap().identifier,
+            )
+        });
+        assert_eq!(derived, vec![(a, b), (c, a)]);
+    }
+
+    /// Relates consecutive numbers, listing them as neighbours.
+    struct Line {
+        asked: Cell<u64>,
+    }
+
+    impl ConstraintProvider<i32, i32> for Line {
+        fn check(&self, _: &Variable<i32, i32>, _: &i32, _: &Variable<i32, i32>, _: &i32) -> bool {
+            true
+        }
+
+        fn constrains(&self, a: &Variable<i32, i32>, b: &Variable<i32, i32>) -> bool {
+            self.asked.set(self.asked.get() + 1);
+            a.identifier.abs_diff(b.identifier) == 1
+        }
+
+        fn neighbours(&self, a: &Variable<i32, i32>) -> Option<Vec<i32>> {
+            Some(vec![a.identifier - 1, a.identifier + 1, a.identifier + 1])
+        }
+    }
+
+    #[test]
+    fn arcs_only_try_listed_neighbours() {
+        let mut variables = VariableProvider::default();
+        for id in 0..100 {
+            variables.add_var(id, vec![1]).unwrap();
+        }
+        let line = Line {
+            asked: Cell::new(0),
+        };
+        let derived = arcs(&variables, &line);
+        assert_eq!(derived.len(), 198);
+        // Both ends have a missing neighbour, and the duplicate is only tried once.
+        assert_eq!(line.asked.get(), 198);
+    }
+
+    /// Like [`Line`], listing neighbours by [`VariableID`].
+    struct Chain {
+        ids: Vec<VariableID>,
+        asked: Cell<u64>,
+    }
+
+    impl ConstraintProvider<i32, usize> for Chain {
+        fn check(
+            &self,
+            _: &Variable<i32, usize>,
+            _: &i32,
+            _: &Variable<i32, usize>,
+            _: &i32,
+        ) -> bool {
+            true
+        }
+
+        fn constrains(&self, a: &Variable<i32, usize>, b: &Variable<i32, usize>) -> bool {
+            self.asked.set(self.asked.get() + 1);
+            a.identifier.abs_diff(b.identifier) == 1
+        }
+
+        fn neighbour_ids(&self, a: &Variable<i32, usize>) -> Option<Vec<VariableID>> {
+            let at = |index: Option<usize>| index.and_then(|i| self.ids.get(i)).copied();
+            let (before, after) = (at(a.identifier.checked_sub(1)), at(Some(a.identifier + 1)));
+            Some([before, after, after].into_iter().flatten().collect())
+        }
+    }
+
+    #[test]
+    fn arcs_only_try_listed_neighbour_ids() {
+        let mut variables = VariableProvider::default();
+        let ids = (0..100)
+            .map(|id| variables.add_var(id, vec![1]).unwrap())
+            .collect();
+        let chain = Chain {
+            ids,
+            asked: Cell::new(0),
+        };
+        assert_eq!(arcs(&variables, &chain).len(), 198);
+        assert_eq!(chain.asked.get(), 198);
+    }
+
+    #[test]
+    fn ac3_counts_work() {
+        let mut variables = VariableProvider::from([('a', vec![1, 2]), ('b', vec![2])]);
+        let a = variables.find_id('a').unwrap();
+        let b = variables.find_id('b').unwrap();
+        let constraints = HashMap::from([((a, b), new_constraint(|a, b| a == b))]);
+        let statistics = ac3(&mut variables, &[(a, b)], &constraints);
+        assert_eq!(statistics.revisions, 1);
+        assert_eq!(statistics.reductions, 1);
+        // Value 1 checks b's only value, as does value 2.
+        assert_eq!(statistics.checks, 2);
+    }
+
     #[test]
     fn revise_shrinks_domain_based_on_constraints() {
         let variables = VariableProvider::from([('a', vec![1, 2, 3]), ('b', vec![2, 3])]);
@@ -142,7 +483,7 @@ mod test {
 
         let constraints = HashMap::from([((a, b), new_constraint(|a, _| *a < 3))]);
 
-        assert!(revise(&variables, &constraints, a, b));
+        assert_eq!(revise(&variables, &constraints, a, b), vec![3]);
         assert!(
             variables
                 .get_var(a)
@@ -168,7 +509,7 @@ mod test {
 
         let constraints = HashMap::from([((a, b), new_constraint(|a, _| *a < 5))]);
 
-        assert!(!revise(&variables, &constraints, a, b));
+        assert!(revise(&variables, &constraints, a, b).is_empty());
         assert!(
             variables
                 .get_var(a)
@@ -188,12 +529,12 @@ mod test {
     #[test]
     fn revise_does_not_change_domain_without_constraints() {
         let variables = VariableProvider::from([('a', vec![1, 2, 3]), ('b', vec![2, 3])]);
-        let constraints = HashMap::new();
+        let constraints = HashMap::<_, Constraint<i32>>::new();
 
         let a = variables.find_id('a').unwrap();
         let b = variables.find_id('b').unwrap();
 
-        assert!(!revise(&variables, &constraints, a, b));
+        assert!(revise(&variables, &constraints, a, b).is_empty());
         assert!(
             variables
                 .get_var(a)
@@ -219,7 +560,7 @@ mod test {
 
         let constraints = HashMap::from([((a, b), new_constraint(|_, _| false))]);
 
-        assert!(revise(&variables, &constraints, a, b));
+        assert_eq!(revise(&variables, &constraints, a, b), vec![1, 2, 3]);
         assert!(
             !variables.get_var(a).unwrap().is_consistent(),
             "is consistent? {:#?}",
@@ -0,0 +1,173 @@
+use rand::Rng;
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use crate::backtrack::{SearchOptions, Solution, search};
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// A temporary restriction for [`solve_assuming`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Assumption<D> {
+    /// The variable takes exactly this value.
+    Assign(VariableID, D),
+    /// The variable does not take this value.
+    Exclude(VariableID, D),
+}
+
+impl<D> Assumption<D>
+where
+    D: DomainType,
+{
+    /// Restrict the domain in `variables` accordingly.
+    pub(crate) fn apply<K>(&self, variables: &VariableProvider<D, K>)
+    where
+        K: IdentifierType,
+    {
+        match self {
+            Assumption::Assign(v, value) => {
+                if let Some(var) = variables.get_var(*v) {
+                    var.retain(|dv| dv == value);
+                }
+            }
+            Assumption::Exclude(v, value) => {
+                if let Some(var) = variables.get_var(*v) {
+                    var.retain(|dv| dv != value);
+                }
+            }
+        }
+    }
+}
+
+/// Drop `items` one at a time, keeping each drop after which `conflicting` still holds, so
+/// nothing more can be dropped from the result. Calls `conflicting` once per item.
+pub(crate) fn shrink<T>(mut items: Vec<T>, mut conflicting: impl FnMut(&[T]) -> bool) -> Vec<T>
+where
+    T: Clone,
+{
+    let mut index = 0;
+    while index < items.len() {
+        let mut without = items.clone();
+        without.remove(index);
+        if conflicting(&without) {
+            items = without;
+        } else {
+            index += 1;
+        }
+    }
+    items
+}
+
+/// Result of [`solve_assuming`].
+#[derive(Debug)]
+pub struct Assumed<D, K> {
+    pub solution: Solution,
+    /// The solution found, on [`Solution::Consistent`].
+    pub variables: Option<VariableProvider<D, K>>,
+    /// On [`Solution::NoSolution`], a subset of the assumptions that already leaves no solution,
+    /// from which none can be dropped. Empty if the problem has no solution even without any
+    /// assumptions.
+    pub conflict: Vec<Assumption<D>>,
+    /// Summed over the search with every assumption in place and, on [`Solution::NoSolution`],
+    /// the search made each time an assumption was dropped while shrinking the conflict.
+    pub statistics: Statistics,
+}
+
+/// Search for a solution with `assumptions` in place, leaving `variables` untouched, e.g. to ask
+/// whether a map still works out with a wall in some cell.
+///
+/// When there is no solution, the assumptions are dropped one at a time to find which of them
+/// are involved, searching again each time. An assumption is kept if a search without it stops
+/// early at a limit, so [`Assumed::conflict`] may be larger than necessary then.
+#[must_use]
+pub fn solve_assuming<K, D, CP, R>(
+    variables: &VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    assumptions: &[Assumption<D>],
+    options: &SearchOptions,
+) -> Assumed<D, K>
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    let mut statistics = Statistics::default();
+    let mut solve = |assumptions: &[Assumption<D>]| {
+        let mut assumed = variables.clone();
+        for assumption in assumptions {
+            assumption.apply(&assumed);
+        }
+        let (solution, search_statistics) = search(&mut assumed, arcs, constraints, rng, options);
+        statistics += search_statistics;
+        (solution, assumed)
+    };
+
+    let (solution, assumed) = solve(assumptions);
+    let mut conflict = vec![];
+    if solution == Solution::NoSolution {
+        conflict = shrink(assumptions.to_vec(), |without| {
+            solve(without).0 == Solution::NoSolution
+        });
+    }
+
+    Assumed {
+        solution,
+        variables: (solution == Solution::Consistent).then_some(assumed),
+        conflict,
+        statistics,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        backtrack::{SearchOptions, Solution},
+        test_util::{ordered, simple_rng},
+    };
+
+    use super::{Assumption, solve_assuming};
+
+    #[test]
+    fn assumptions_leave_variables_alone() {
+        let mut rng = simple_rng("assumptions");
+        let (variables, arcs, constraints) = ordered();
+        let [a, b, c] = ['a', 'b', 'c'].map(|id| variables.find_id(id).unwrap());
+        let options = SearchOptions::default();
+
+        let assumed = solve_assuming(
+            &variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &[Assumption::Exclude(c, 4)],
+            &options,
+        );
+        assert_eq!(assumed.solution, Solution::Consistent);
+        let solved = assumed.variables.unwrap();
+        assert_eq!(solved.get_var(a).unwrap().possible_values()[0], 1);
+        assert_eq!(solved.get_var(c).unwrap().possible_values()[0], 3);
+        assert_eq!(variables.get_var(c).unwrap().possible_values().len(), 4);
+
+        let assumptions = [
+            Assumption::Assign(b, 2),
+            Assumption::Exclude(c, 3),
+            Assumption::Assign(c, 2),
+            Assumption::Exclude(a, 4),
+        ];
+        let assumed = solve_assuming(
+            &variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &assumptions,
+            &options,
+        );
+        assert_eq!(assumed.solution, Solution::NoSolution);
+        assert!(assumed.variables.is_none());
+        // c = 2 alone is enough to fail.
+        assert_eq!(assumed.conflict, vec![Assumption::Assign(c, 2)]);
+        assert_eq!(variables.get_var(b).unwrap().possible_values().len(), 4);
+    }
+}
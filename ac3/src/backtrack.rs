@@ -1,116 +1,839 @@
-use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::time::Instant;
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType, propagate};
+use crate::limits::{CancellationToken, Limit, Limits};
+use crate::nogood::{Nogood, NogoodCheck, NogoodPolicy, Nogoods};
+use crate::observer::SearchObserver;
+use crate::restart::RestartPolicy;
+use crate::statistics::Statistics;
 use crate::variable_provider::{VariableID, VariableProvider};
-use rand::rngs::SmallRng;
+use rand::Rng;
 use rand::seq::IndexedRandom;
 
 /// Tracking state for the search algorithm to undo itself.
 #[derive(Debug)]
 struct State<D, K> {
     variable_id: VariableID,
+    /// The value currently being tried.
+    value: D,
     variables: VariableProvider<D, K>,
     untested: Vec<D>,
+    /// Copy of [`Solver::conflicts`] from before the decision, when tracked.
+    conflicts: Option<Conflicts>,
+    /// Decision levels responsible for every value of this variable that was tried or pruned.
+    conflict: BTreeSet<usize>,
+    /// Values tried so far on this path that were not the first choice.
+    discrepancies: u64,
+    /// Set when the discrepancy limit left part of the tree below this decision unexplored.
+    cut: bool,
+}
+
+/// Conflict sets used for backjumping and nogood learning. Decision levels count from 1, for the
+/// first decision on the stack.
+#[derive(Clone, Debug, Default)]
+struct Conflicts {
+    /// Decision levels responsible for values missing from each variable's domain.
+    sets: HashMap<VariableID, BTreeSet<usize>>,
+    /// Decision level of each decided variable.
+    levels: HashMap<VariableID, usize>,
+}
+
+impl Conflicts {
+    /// Decision levels that led to the current domain of `variable`.
+    fn explain(&self, variable: VariableID) -> BTreeSet<usize> {
+        let mut levels = self.sets.get(&variable).cloned().unwrap_or_default();
+        levels.extend(self.levels.get(&variable));
+        levels
+    }
+
+    /// Record that values were removed from `x` because of the domain of `y`.
+    fn blame(&mut self, x: VariableID, y: VariableID) {
+        let levels = self.explain(y);
+        self.sets.entry(x).or_default().extend(levels);
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Solution {
     Consistent,
     NoSolution,
+    /// The search stopped early, without determining whether a solution exists.
+    LimitReached(Limit),
+}
+
+/// Arc weights for [`VariableOrdering::DomWdeg`]. They are bumped on every wipeout and never
+/// undone, so they carry what the search learned across backtracks and restarts.
+#[derive(Debug)]
+struct Weights {
+    arcs: HashMap<(VariableID, VariableID), usize>,
+    by_variable: HashMap<VariableID, Vec<usize>>,
+    counts: Vec<u64>,
+}
+
+impl Weights {
+    fn new(arcs: &[(VariableID, VariableID)]) -> Self {
+        let mut weights = Self {
+            arcs: HashMap::new(),
+            by_variable: HashMap::new(),
+            counts: vec![1; arcs.len()],
+        };
+        for (index, (x, y)) in arcs.iter().enumerate() {
+            weights.arcs.insert((*x, *y), index);
+            weights.by_variable.entry(*x).or_default().push(index);
+            weights.by_variable.entry(*y).or_default().push(index);
+        }
+        weights
+    }
+
+    fn bump(&mut self, arc: (VariableID, VariableID)) {
+        if let Some(index) = self.arcs.get(&arc) {
+            self.counts[*index] += 1;
+        }
+    }
+
+    fn weighted_degree(&self, variable: VariableID) -> u64 {
+        self.by_variable
+            .get(&variable)
+            .map_or(0, |arcs| arcs.iter().map(|index| self.counts[*index]).sum())
+    }
+
+    /// The unsolved variable with the smallest ratio of domain size to weighted degree.
+    fn select<D, K>(&self, variables: &VariableProvider<D, K>) -> Option<VariableID>
+    where
+        D: DomainType,
+        K: IdentifierType,
+    {
+        variables
+            .iter()
+            .filter_map(|v| {
+                let len = v.possible_values().len() as u64;
+                (len > 1).then(|| (len, self.weighted_degree(v.index).max(1), v))
+            })
+            // Compare len_a / wdeg_a against len_b / wdeg_b without dividing.
+            .min_by(|(a_len, a_wdeg, a), (b_len, b_wdeg, b)| {
+                (u128::from(*a_len) * u128::from(*b_wdeg))
+                    .cmp(&(u128::from(*b_len) * u128::from(*a_wdeg)))
+                    .then(a.identifier.cmp(&b.identifier))
+            })
+            .map(|(_, _, v)| v.index)
+    }
+}
+
+/// How the search picks the next variable to decide.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VariableOrdering {
+    /// The variable with the fewest possible values, ties broken by identifier.
+    #[default]
+    MinDomain,
+    /// The variable with the fewest possible values relative to how often its arcs caused a
+    /// wipeout, which steers the search towards the hard parts of the problem first.
+    DomWdeg,
+}
+
+/// How much propagation the search runs after each decision.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Propagation {
+    /// Only check constraints between variables that are down to a single value, i.e. plain
+    /// backtracking.
+    None,
+    /// Remove values that conflict with variables down to a single value, without looking any
+    /// further.
+    ForwardChecking,
+    /// Maintain arc consistency over every arc, the same as running [`crate::ac3::ac3`].
+    #[default]
+    Mac,
+}
+
+impl Propagation {
+    /// Whether the arc `(x, y)` is worth revising at this level.
+    fn revises<D, K>(
+        self,
+        variables: &VariableProvider<D, K>,
+        (x, y): (VariableID, VariableID),
+    ) -> bool
+    where
+        D: DomainType,
+        K: IdentifierType,
+    {
+        let decided = |v| {
+            variables
+                .get_var(v)
+                .is_some_and(|var| var.possible_values().len() == 1)
+        };
+        match self {
+            Propagation::None => decided(y) && decided(x),
+            Propagation::ForwardChecking => decided(y),
+            Propagation::Mac => true,
+        }
+    }
+}
+
+/// The order in which the search visits the tree of decisions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Plain depth first search.
+    #[default]
+    DepthFirst,
+    /// [Limited discrepancy search](https://www.ijcai.org/Proceedings/95-1/Papers/080.pdf):
+    /// explore only paths that stray from the first choice of [`ValueOrdering`] at most a limited
+    /// number of times, starting from none and allowing one more each time the tree is exhausted.
+    /// This finds solutions near the heuristic's choices first. Later iterations go over the same
+    /// paths again, so resuming past solutions can repeat them, especially with
+    /// [`ValueOrdering::Random`] where the first choice differs between iterations.
+    LimitedDiscrepancy,
+}
+
+/// How the search picks the value to try for a variable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValueOrdering {
+    /// A random possible value.
+    #[default]
+    Random,
+    /// The first possible value, in domain order. The random number generator is never used, so
+    /// the result only depends on the problem.
+    First,
+}
+
+impl ValueOrdering {
+    fn pick<D, R>(self, values: &[D], rng: &mut R) -> Option<D>
+    where
+        D: Copy,
+        R: Rng + ?Sized,
+    {
+        match self {
+            ValueOrdering::Random => values.choose(rng).copied(),
+            ValueOrdering::First => values.first().copied(),
+        }
+    }
+}
+
+/// Tuning for [`search`]. The default is an unbounded search, the same as [`reduce`].
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions {
+    pub limits: Limits,
+    /// Checked between steps, the search stops with [`Limit::Cancelled`] once it is cancelled.
+    pub cancellation: Option<CancellationToken>,
+    /// Track which decisions caused each domain reduction, so a wipeout undoes every decision
+    /// back to the deepest one actually responsible instead of only the most recent.
+    pub backjumping: bool,
+    /// Start over from the root whenever the policy's backtrack budget runs out. The search can
+    /// still prove there is no solution, but only once a single attempt exhausts its tree. Restarts
+    /// stop once a solution is found, so resuming past it never finds it again.
+    pub restarts: Option<RestartPolicy>,
+    pub variable_ordering: VariableOrdering,
+    pub value_ordering: ValueOrdering,
+    pub propagation: Propagation,
+    pub strategy: Strategy,
+    /// Remember the combinations of decisions behind each failure, and prune them from the rest
+    /// of the search, including after restarts.
+    pub nogoods: Option<NogoodPolicy>,
+}
+
+/// A resumable depth first search over the domains of a [`VariableProvider`], for callers that
+/// cannot afford to run [`reduce`] to completion in one go, e.g. a game generating a map over
+/// several frames. Each [`Solver::step`] propagates once and then makes or undoes one decision,
+/// and [`Solver::variables`] shows the domains in between.
+///
+/// Stepping on after [`Solution::Consistent`] rejects that solution and looks for the next one.
+///
+/// Any [`Rng`] works. The same seed reproduces the same search for a given generator, so pick a
+/// portable one (e.g. `ChaCha8Rng`) when maps must match across machines and `rand` versions, or use
+/// [`ValueOrdering::First`] to not depend on randomness at all.
+pub struct Solver<'a, D, K, CP, R: ?Sized, O = ()> {
+    variables: VariableProvider<D, K>,
+    arcs: &'a [(VariableID, VariableID)],
+    constraints: &'a CP,
+    rng: &'a mut R,
+    observer: O,
+    backtrack: Vec<State<D, K>>,
+    options: SearchOptions,
+    /// When the search started, only read from the clock under a time limit since some targets
+    /// have none.
+    started: Option<Instant>,
+    statistics: Statistics,
+    /// Present when backjumping or learning nogoods.
+    conflicts: Option<Conflicts>,
+    nogoods: Option<Nogoods<D>>,
+    /// Present when ordering by weighted degree.
+    weights: Option<Weights>,
+    /// Value of [`Statistics::backtracks`] at the last restart.
+    backtracks_at_restart: u64,
+    /// Set when the current branch is inconsistent or was already returned as a solution, so the
+    /// next step must undo a decision.
+    dead_end: bool,
+    /// Decision levels responsible for the dead end, if known.
+    conflict: Option<BTreeSet<usize>>,
+    /// Set once every branch has been visited.
+    exhausted: bool,
+    /// Discrepancies allowed on a path, for [`Strategy::LimitedDiscrepancy`].
+    discrepancy_limit: Option<u64>,
+    /// Set when the current discrepancy limit cut anything off since the search started over.
+    discrepancy_cut: bool,
+}
+
+impl<'a, D, K, CP, R> Solver<'a, D, K, CP, R>
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    #[must_use]
+    pub fn new(
+        variables: VariableProvider<D, K>,
+        arcs: &'a [(VariableID, VariableID)],
+        constraints: &'a CP,
+        rng: &'a mut R,
+        options: &SearchOptions,
+    ) -> Self {
+        Self {
+            variables,
+            arcs,
+            constraints,
+            rng,
+            observer: (),
+            backtrack: vec![],
+            options: options.clone(),
+            started: options.limits.time.map(|_| Instant::now()),
+            statistics: Statistics::default(),
+            conflicts: (options.backjumping || options.nogoods.is_some()).then(Conflicts::default),
+            nogoods: options.nogoods.map(Nogoods::new),
+            weights: (options.variable_ordering == VariableOrdering::DomWdeg)
+                .then(|| Weights::new(arcs)),
+            backtracks_at_restart: 0,
+            dead_end: false,
+            conflict: None,
+            exhausted: false,
+            discrepancy_limit: (options.strategy == Strategy::LimitedDiscrepancy).then_some(0),
+            discrepancy_cut: false,
+        }
+    }
 }
 
-// TODO: Should this be "externalized"? Like is there any reason to run CSP outside?
 // TODO: Ideally we'd only store the diff in the state for pop/push, not a whole copy.
-#[must_use]
-pub fn reduce<K, D, CP>(
-    variables: &mut VariableProvider<D, K>,
-    arcs: &mut [(VariableID, VariableID)],
-    constraints: &CP,
-    rng: &mut SmallRng,
-) -> Solution
+impl<'a, D, K, CP, R, O> Solver<'a, D, K, CP, R, O>
 where
     K: IdentifierType,
     D: DomainType,
     CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+    O: SearchObserver<D, K>,
 {
-    let mut backtrack: Vec<State<D, K>> = vec![];
+    /// Report search events to `observer` from now on.
+    #[must_use]
+    pub fn with_observer<O2>(self, observer: O2) -> Solver<'a, D, K, CP, R, O2>
+    where
+        O2: SearchObserver<D, K>,
+    {
+        Solver {
+            variables: self.variables,
+            arcs: self.arcs,
+            constraints: self.constraints,
+            rng: self.rng,
+            observer,
+            backtrack: self.backtrack,
+            options: self.options,
+            started: self.started,
+            statistics: self.statistics,
+            conflicts: self.conflicts,
+            nogoods: self.nogoods,
+            weights: self.weights,
+            backtracks_at_restart: self.backtracks_at_restart,
+            dead_end: self.dead_end,
+            conflict: self.conflict,
+            exhausted: self.exhausted,
+            discrepancy_limit: self.discrepancy_limit,
+            discrepancy_cut: self.discrepancy_cut,
+        }
+    }
+
+    /// The current domains. Only a [`Solution::Consistent`] result guarantees every variable has
+    /// exactly one value.
+    #[must_use]
+    pub fn variables(&self) -> &VariableProvider<D, K> {
+        &self.variables
+    }
+
+    #[must_use]
+    pub fn into_variables(self) -> VariableProvider<D, K> {
+        self.variables
+    }
+
+    #[must_use]
+    pub fn statistics(&self) -> Statistics {
+        self.statistics
+    }
+
+    /// Discrepancies currently allowed, if [`SearchOptions::strategy`] is
+    /// [`Strategy::LimitedDiscrepancy`].
+    #[must_use]
+    pub fn discrepancy_limit(&self) -> Option<u64> {
+        self.discrepancy_limit
+    }
+
+    /// Nogoods learned so far, if [`SearchOptions::nogoods`] is set.
+    #[must_use]
+    pub fn nogoods(&self) -> &[Nogood<D>] {
+        self.nogoods.as_ref().map_or(&[], Nogoods::as_slice)
+    }
+
+    /// Number of decisions currently in effect.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.backtrack.len()
+    }
+
+    /// Do one round of propagation followed by one decision or backtrack. Returns `None` while
+    /// there is more work to do.
+    pub fn step(&mut self) -> Option<Solution> {
+        self.step_pruned(&mut |_| false)
+    }
+
+    /// Take up to `steps` steps, stopping early once the search produces a result.
+    pub fn run_for(&mut self, steps: usize) -> Option<Solution> {
+        (0..steps).find_map(|_| self.step())
+    }
+
+    /// Step until the search produces a result.
+    pub fn run(&mut self) -> Solution {
+        self.next_solution(|_| false)
+    }
+
+    /// Step until the search produces a result. `prune` is consulted after every propagation, any
+    /// branch it returns `true` for is abandoned as though it were inconsistent.
+    pub(crate) fn next_solution<F>(&mut self, mut prune: F) -> Solution
+    where
+        F: FnMut(&VariableProvider<D, K>) -> bool,
+    {
+        loop {
+            if let Some(solution) = self.step_pruned(&mut prune) {
+                return solution;
+            }
+        }
+    }
+
+    fn step_pruned(
+        &mut self,
+        prune: &mut dyn FnMut(&VariableProvider<D, K>) -> bool,
+    ) -> Option<Solution> {
+        if self.exhausted {
+            return Some(Solution::NoSolution);
+        }
+
+        if self.dead_end {
+            if self.backtrack.is_empty() {
+                self.exhausted = true;
+                return Some(Solution::NoSolution);
+            }
+            if let Some(limit) = self.limit_reached() {
+                return Some(Solution::LimitReached(limit));
+            }
+            self.dead_end = false;
+            if self.restart_due() {
+                self.restart();
+            } else if !self.backtrack() {
+                self.exhausted = true;
+                return Some(Solution::NoSolution);
+            }
+        }
 
-    loop {
         // First, make domains consistent.
-        crate::ac3::ac3(variables, arcs, constraints);
-
-        // Second, search for the most constrained unsolved variable and try to choose a value for it.
-        if let Some(v) = variables.next_reducable_variable() {
-            let variables_clone = variables.clone();
-            if let Some(reducable) = variables.get_var(v) {
-                // Use a random index for selecting the answer, and store the selection.
-                if let Some(selected) = reducable.choose(rng) {
-                    let untested = reducable
-                        .possible_values()
+        let consistent = self.make_consistent();
+
+        // Then either give up on this branch, try to reduce a variable further, or stop
+        // because everything is reduced and consistent.
+        if !consistent || !self.variables.is_consistent() || prune(&self.variables) {
+            self.dead_end = true;
+        } else if let Some(v) = self.select_variable() {
+            if let Some(limit) = self.limit_reached() {
+                return Some(Solution::LimitReached(limit));
+            }
+            self.decide(v);
+        } else {
+            // Resuming moves on to the next solution rather than returning this one again.
+            self.dead_end = true;
+            // Restarting from here could find this solution again.
+            self.options.restarts = None;
+            self.observer.on_solution(&self.variables);
+            return Some(Solution::Consistent);
+        }
+        None
+    }
+
+    /// Run AC-3 and the learned nogoods until neither removes anything else. Returns `false` if
+    /// that fails, leaving [`Solver::conflict`] to explain why when conflicts are tracked.
+    fn make_consistent(&mut self) -> bool {
+        let level = self.options.propagation;
+        let mut queue = self
+            .arcs
+            .iter()
+            .copied()
+            .filter(|arc| level.revises(&self.variables, *arc))
+            .collect::<VecDeque<_>>();
+
+        loop {
+            let observer = &mut self.observer;
+            let conflicts = &mut self.conflicts;
+            let variables = &self.variables;
+            let wipeout = propagate(
+                &self.variables,
+                self.arcs,
+                self.constraints,
+                queue,
+                &mut self.statistics,
+                |x, y, removed| {
+                    if let Some(neighbour) = variables.get_var(y) {
+                        observer.on_revision(x, removed, y, &neighbour.possible_values());
+                    }
+                    observer.on_reduction(x, removed);
+                    if let Some(conflicts) = conflicts {
+                        conflicts.blame(x, y);
+                    }
+                },
+                |arc| level.revises(variables, *arc),
+            );
+            if let Some((x, y)) = wipeout {
+                self.observer.on_wipeout(x);
+                self.conflict = self.conflicts.as_ref().map(|c| c.explain(x));
+                if let Some(weights) = &mut self.weights {
+                    weights.bump((x, y));
+                }
+                return false;
+            }
+
+            match self.nogoods.as_mut().and_then(|n| n.check(&self.variables)) {
+                None => return true,
+                Some(NogoodCheck::Prune {
+                    variable,
+                    value,
+                    because,
+                }) => {
+                    if let Some(var) = self.variables.get_var(variable) {
+                        var.retain(|dv| *dv != value);
+                    }
+                    self.observer.on_reduction(variable, &[value]);
+                    if let Some(conflicts) = &mut self.conflicts {
+                        for other in because {
+                            conflicts.blame(variable, other);
+                        }
+                    }
+                    // Only arcs pointing at the reduced variable need another look.
+                    queue = self
+                        .arcs
                         .iter()
-                        .filter(|dv| selected != **dv)
+                        .filter(|(_, y)| *y == variable)
                         .copied()
+                        .filter(|arc| level.revises(&self.variables, *arc))
                         .collect();
-                    backtrack.push(State {
-                        variable_id: reducable.index,
-                        variables: variables_clone,
-                        untested,
-                    });
-                    reducable.retain(|dv| *dv == selected);
-                } else {
-                    println!("This should never happen");
                 }
-            } else {
-                println!("This should not happen?");
+                Some(NogoodCheck::Violated { because }) => {
+                    self.conflict = self
+                        .conflicts
+                        .as_ref()
+                        .map(|c| because.iter().flat_map(|v| c.explain(*v)).collect());
+                    return false;
+                }
             }
         }
-        // Alternatively, if there are no viable selections to be made
-        else if !variables.is_consistent() {
-            let len = backtrack.len();
-            let Some(mut prev) = backtrack.pop() else {
-                return Solution::NoSolution;
-            };
+    }
 
-            // Choose from the untested values, if there are any.
-            if let Some(selected) = prev.untested.choose(rng).copied() {
-                prev.untested.retain(|dv| *dv != selected);
-                if let Some(v) = variables.get_var(prev.variable_id) {
-                    v.replace_possible_values(vec![selected]);
+    /// Record the decisions at the levels in `conflict`, along with the current decision in
+    /// `state`, as a nogood.
+    fn learn(&mut self, conflict: &BTreeSet<usize>, state: &State<D, K>) {
+        let Some(nogoods) = &mut self.nogoods else {
+            return;
+        };
+        let assignments = conflict
+            .iter()
+            .filter_map(|level| self.backtrack.get(level - 1))
+            .chain(std::iter::once(state))
+            .map(|s| (s.variable_id, s.value))
+            .collect();
+        if nogoods.learn(assignments) {
+            self.statistics.nogoods += 1;
+        }
+    }
+
+    fn limit_reached(&self) -> Option<Limit> {
+        let Limits {
+            nodes,
+            backtracks,
+            time,
+        } = &self.options.limits;
+
+        if self
+            .options
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            Some(Limit::Cancelled)
+        } else if nodes.is_some_and(|max| self.statistics.decisions >= max) {
+            Some(Limit::Nodes)
+        } else if backtracks.is_some_and(|max| self.statistics.backtracks >= max) {
+            Some(Limit::Backtracks)
+        } else if time
+            .zip(self.started)
+            .is_some_and(|(max, started)| started.elapsed() >= max)
+        {
+            Some(Limit::Time)
+        } else {
+            None
+        }
+    }
+
+    fn select_variable(&mut self) -> Option<VariableID> {
+        match &self.weights {
+            Some(weights) => weights.select(&self.variables),
+            None => self.variables.next_reducable_variable(),
+        }
+    }
+
+    fn restart_due(&self) -> bool {
+        self.options.restarts.is_some_and(|policy| {
+            self.statistics.backtracks - self.backtracks_at_restart
+                >= policy.limit(self.statistics.restarts)
+        })
+    }
+
+    /// Undo every decision, keeping the weights and the random number generator as they are.
+    fn restart(&mut self) {
+        if let Some(root) = self.backtrack.drain(..).next() {
+            self.variables = root.variables;
+            self.conflicts = root.conflicts;
+        }
+        self.conflict = None;
+        self.discrepancy_cut = false;
+        self.statistics.restarts += 1;
+        self.backtracks_at_restart = self.statistics.backtracks;
+        self.observer.on_restart();
+    }
+
+    /// Choose a value for `v`, remembering the alternatives for later.
+    fn decide(&mut self, v: VariableID) {
+        let variables_clone = self.variables.clone();
+        let conflicts_clone = self.conflicts.clone();
+        let Some(reducable) = self.variables.get_var(v) else {
+            return;
+        };
+        let selected = self
+            .options
+            .value_ordering
+            .pick(&reducable.possible_values(), self.rng);
+        if let Some(selected) = selected {
+            let untested = reducable
+                .possible_values()
+                .iter()
+                .filter(|dv| selected != **dv)
+                .copied()
+                .collect();
+            let discrepancies = self.backtrack.last().map_or(0, |s| s.discrepancies);
+            self.backtrack.push(State {
+                variable_id: reducable.index,
+                value: selected,
+                discrepancies,
+                cut: false,
+                variables: variables_clone,
+                untested,
+                conflict: self
+                    .conflicts
+                    .as_ref()
+                    .map(|c| c.explain(v))
+                    .unwrap_or_default(),
+                conflicts: conflicts_clone,
+            });
+            if let Some(conflicts) = &mut self.conflicts {
+                conflicts.levels.insert(v, self.backtrack.len());
+            }
+            reducable.retain(|dv| *dv == selected);
+            self.observer.on_decision(v, &selected);
+            self.statistics.decisions += 1;
+            self.statistics.max_depth = self.statistics.max_depth.max(self.backtrack.len());
+        }
+    }
+
+    /// Restore the most recent decision that still has untested values and select one of them.
+    /// When backjumping, decisions that played no part in the conflict are skipped over. Returns
+    /// `false` if there is nothing left to try.
+    fn backtrack(&mut self) -> bool {
+        self.statistics.backtracks += 1;
+        // Without a better explanation, every decision so far is to blame.
+        let mut conflict = self.conflicts.is_some().then(|| {
+            self.conflict
+                .take()
+                .unwrap_or_else(|| (1..=self.backtrack.len()).collect())
+        });
+
+        // Limited discrepancy search starts over from here once the tree is exhausted.
+        let mut root = None;
+
+        while let Some(mut prev) = self.backtrack.pop() {
+            let level = self.backtrack.len() + 1;
+            if level == 1 && self.discrepancy_limit.is_some() {
+                root = Some((prev.variables.clone(), prev.conflicts.clone()));
+            }
+            if let Some(conflict) = &mut conflict {
+                if conflict.remove(&level) {
+                    // A nogood is only proven if nothing below was left unexplored.
+                    if !prev.cut {
+                        self.learn(conflict, &prev);
+                    }
+                } else if self.options.backjumping {
+                    continue;
                 }
-                backtrack.push(prev);
+                prev.conflict.append(conflict);
             }
-            // If there are no untested values, go back to the top of the backtrack and reset the domain.
-            else if len > 1 {
-                // TODO: setup top level as unfiltered so this is easier to steal prev
-                // values without cloning whole solution space?
-                variables.clone_from(&prev.variables);
+
+            // Choose from the untested values, if there are any and another discrepancy is
+            // allowed, otherwise keep unwinding.
+            let discrepancies = self.backtrack.last().map_or(0, |s| s.discrepancies) + 1;
+            let selected = if self
+                .discrepancy_limit
+                .is_none_or(|limit| discrepancies <= limit)
+            {
+                self.options.value_ordering.pick(&prev.untested, self.rng)
+            } else {
+                None
+            };
+            if let Some(selected) = selected {
+                prev.untested.retain(|dv| *dv != selected);
+                prev.value = selected;
+                prev.discrepancies = discrepancies;
+                self.variables.clone_from(&prev.variables);
+                self.variables.update_var(prev.variable_id, vec![selected]);
+                self.conflicts.clone_from(&prev.conflicts);
+                if let Some(conflicts) = &mut self.conflicts {
+                    conflicts.levels.insert(prev.variable_id, level);
+                }
+                self.observer.on_backtrack(prev.variable_id);
+                self.observer.on_decision(prev.variable_id, &selected);
+                self.backtrack.push(prev);
+                self.statistics.decisions += 1;
+                return true;
             }
-            // If we're popping the top of the backtrack stack, we're done. Cooked.
-            else {
-                return Solution::NoSolution;
+
+            if prev.cut || !prev.untested.is_empty() {
+                // Values were left untried, so only every earlier decision explains giving up.
+                self.discrepancy_cut = true;
+                if let Some(below) = self.backtrack.last_mut() {
+                    below.cut = true;
+                }
+                if let Some(conflict) = &mut conflict {
+                    *conflict = (1..level).collect();
+                }
+            } else if let Some(conflict) = &mut conflict {
+                // Every value failed, so the next decision to revisit is whichever is responsible
+                // for those failures.
+                *conflict = std::mem::take(&mut prev.conflict);
             }
         }
-        // Finally, if we've exhausted all reducable variables, and everything is consistent, we must have found a solution.
-        else {
-            return Solution::Consistent;
+
+        if self.discrepancy_cut
+            && let Some(limit) = &mut self.discrepancy_limit
+            && let Some((variables, conflicts)) = root
+        {
+            *limit += 1;
+            self.discrepancy_cut = false;
+            self.variables = variables;
+            self.conflicts = conflicts;
+            self.observer.on_restart();
+            return true;
         }
+        false
     }
 }
 
+/// Search for a single value for every variable that satisfies all constraints. On
+/// [`Solution::Consistent`] each variable in `variables` is left with exactly one possible value.
+///
+/// The returned [`Statistics`] describe how much work the search took.
+// TODO: Should this be "externalized"? Like is there any reason to run CSP outside?
+#[must_use]
+pub fn reduce<K, D, CP, R>(
+    variables: &mut VariableProvider<D, K>,
+    arcs: &mut [(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+) -> (Solution, Statistics)
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    search(variables, arcs, constraints, rng, &SearchOptions::default())
+}
+
+/// Same as [`reduce`], but tuned by `options`. Searches cut short by [`SearchOptions::limits`] or
+/// [`SearchOptions::cancellation`] return [`Solution::LimitReached`], leaving `variables` partially
+/// reduced.
+#[must_use]
+pub fn search<K, D, CP, R>(
+    variables: &mut VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    options: &SearchOptions,
+) -> (Solution, Statistics)
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    search_observed(variables, arcs, constraints, rng, options, &mut ())
+}
+
+/// Same as [`search`], reporting decisions, domain reductions, wipeouts, backtracks and the
+/// solution to `observer` as they happen.
+#[must_use]
+pub fn search_observed<K, D, CP, R, O>(
+    variables: &mut VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    options: &SearchOptions,
+    observer: &mut O,
+) -> (Solution, Statistics)
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+    O: SearchObserver<D, K>,
+{
+    let mut solver = Solver::new(std::mem::take(variables), arcs, constraints, rng, options)
+        .with_observer(observer);
+    let solution = solver.run();
+    let statistics = solver.statistics();
+    *variables = solver.into_variables();
+    (solution, statistics)
+}
+
 #[cfg(test)]
 mod test {
-    use rand::rngs::SmallRng;
-    use rand_seeder::Seeder;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use std::collections::HashSet;
+    use std::time::Duration;
 
     use crate::{
         ac3::ConstraintProvider,
         backtrack::Solution,
+        limits::{CancellationToken, Limit, Limits},
+        nogood::NogoodPolicy,
+        observer::SearchObserver,
+        restart::RestartPolicy,
+        test_util::{all_different, simple_rng},
         variable_provider::{Variable, VariableID, VariableProvider},
     };
 
-    use super::reduce;
-
-    fn simple_rng(seed_str: &str) -> SmallRng {
-        Seeder::from(seed_str).into_rng()
-    }
+    use super::{
+        Propagation, SearchOptions, Solver, Strategy, ValueOrdering, VariableOrdering, Weights,
+        reduce, search, search_observed,
+    };
 
     struct TestCase {
         arcs: Vec<(VariableID, VariableID)>,
@@ -183,7 +906,7 @@ mod test {
 
         assert_eq!(
             Solution::Consistent,
-            reduce(&mut variables, &mut arcs, &StaticConstraints {}, &mut rng,)
+            reduce(&mut variables, &mut arcs, &StaticConstraints {}, &mut rng,).0
         );
         println!(
             "{}, {}",
@@ -202,6 +925,660 @@ mod test {
         );
     }
 
+    #[test]
+    fn search_accepts_any_rng() {
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build_test_case();
+        let mut rng = StdRng::seed_from_u64(7);
+        let (solution, _) = search(
+            &mut variables,
+            &arcs,
+            &StaticConstraints {},
+            &mut rng,
+            &SearchOptions::default(),
+        );
+        assert_eq!(solution, Solution::Consistent);
+    }
+
+    #[test]
+    fn first_value_ordering_ignores_the_rng() {
+        let solve = |seed| {
+            let TestCase {
+                arcs,
+                mut variables,
+            } = build_test_case();
+            let options = SearchOptions {
+                value_ordering: ValueOrdering::First,
+                ..SearchOptions::default()
+            };
+            let mut rng = simple_rng(seed);
+            let (solution, _) = search(
+                &mut variables,
+                &arcs,
+                &StaticConstraints {},
+                &mut rng,
+                &options,
+            );
+            assert_eq!(solution, Solution::Consistent);
+            (0..6)
+                .map(|id| variables.find_var(id).unwrap().possible_values()[0])
+                .collect::<String>()
+        };
+
+        assert_eq!(solve("one"), "abacab");
+        assert_eq!(solve("two"), "abacab");
+    }
+
+    /// Four variables that must all differ, but only three values to go around.
+    struct Pigeons {}
+    impl ConstraintProvider<char, i32> for Pigeons {
+        fn check(
+            &self,
+            _: &Variable<char, i32>,
+            a: &char,
+            _: &Variable<char, i32>,
+            b: &char,
+        ) -> bool {
+            a != b
+        }
+    }
+
+    fn build_pigeons() -> TestCase {
+        let (variables, arcs, _) = all_different(&[0, 1, 2, 3], &['a', 'b', 'c']);
+        TestCase { arcs, variables }
+    }
+
+    #[test]
+    fn exhausted_search_has_no_solution() {
+        let mut rng = simple_rng("pigeons");
+        let TestCase {
+            mut arcs,
+            mut variables,
+        } = build_pigeons();
+
+        assert_eq!(
+            Solution::NoSolution,
+            reduce(&mut variables, &mut arcs, &Pigeons {}, &mut rng).0
+        );
+    }
+
+    #[test]
+    fn reduce_collects_statistics() {
+        let mut rng = simple_rng("statistics");
+        let TestCase {
+            mut arcs,
+            mut variables,
+        } = build_pigeons();
+
+        let (_, statistics) = reduce(&mut variables, &mut arcs, &Pigeons {}, &mut rng);
+
+        // Two decisions always leave the last pair of pigeons fighting over one hole.
+        assert!(statistics.decisions >= 2);
+        assert!(statistics.backtracks > 0);
+        assert_eq!(statistics.max_depth, 2);
+        assert!(statistics.checks > statistics.revisions);
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        decisions: Vec<(VariableID, char)>,
+        reductions: usize,
+        wipeouts: usize,
+        backtracks: usize,
+        restarts: usize,
+        solutions: usize,
+    }
+
+    impl SearchObserver<char, i32> for Recorder {
+        fn on_decision(&mut self, variable: VariableID, value: &char) {
+            self.decisions.push((variable, *value));
+        }
+
+        fn on_reduction(&mut self, _: VariableID, removed: &[char]) {
+            assert!(!removed.is_empty());
+            self.reductions += 1;
+        }
+
+        fn on_wipeout(&mut self, _: VariableID) {
+            self.wipeouts += 1;
+        }
+
+        fn on_backtrack(&mut self, _: VariableID) {
+            self.backtracks += 1;
+        }
+
+        fn on_restart(&mut self) {
+            self.restarts += 1;
+        }
+
+        fn on_solution(&mut self, _: &VariableProvider<char, i32>) {
+            self.solutions += 1;
+        }
+    }
+
+    #[test]
+    fn observer_sees_search_events() {
+        let mut rng = simple_rng("observer");
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build_pigeons();
+        let mut recorder = Recorder::default();
+
+        let (solution, statistics) = search_observed(
+            &mut variables,
+            &arcs,
+            &Pigeons {},
+            &mut rng,
+            &SearchOptions::default(),
+            &mut recorder,
+        );
+
+        assert_eq!(solution, Solution::NoSolution);
+        assert_eq!(recorder.decisions.len() as u64, statistics.decisions);
+        assert_eq!(recorder.reductions as u64, statistics.reductions);
+        assert!(recorder.wipeouts > 0);
+        assert!(recorder.backtracks > 0);
+        assert_eq!(recorder.solutions, 0);
+
+        let mut rng = simple_rng("observer");
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build_test_case();
+        let mut recorder = Recorder::default();
+        let (solution, _) = search_observed(
+            &mut variables,
+            &arcs,
+            &StaticConstraints {},
+            &mut rng,
+            &SearchOptions::default(),
+            &mut recorder,
+        );
+        assert_eq!(solution, Solution::Consistent);
+        assert_eq!(recorder.solutions, 1);
+        for (variable, value) in recorder.decisions {
+            assert!(variables.get_var(variable).is_some());
+            assert!(value.is_ascii_lowercase());
+        }
+    }
+
+    #[test]
+    fn solver_steps_to_the_same_solution_as_search() {
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build_test_case();
+        let mut rng = simple_rng("steps");
+        let mut solver = Solver::new(
+            variables.clone(),
+            &arcs,
+            &StaticConstraints {},
+            &mut rng,
+            &SearchOptions::default(),
+        );
+
+        assert_eq!(solver.run_for(1), None);
+        assert_eq!(solver.depth(), 1);
+        let mut steps = 1;
+        let solution = loop {
+            steps += 1;
+            if let Some(solution) = solver.step() {
+                break solution;
+            }
+        };
+        assert_eq!(solution, Solution::Consistent);
+        assert_eq!(solver.statistics().decisions + 1, steps);
+
+        let mut rng = simple_rng("steps");
+        let (solution, _) = search(
+            &mut variables,
+            &arcs,
+            &StaticConstraints {},
+            &mut rng,
+            &SearchOptions::default(),
+        );
+        assert_eq!(solution, Solution::Consistent);
+        for id in 0..6 {
+            assert_eq!(
+                *solver.variables().find_var(id).unwrap().possible_values(),
+                *variables.find_var(id).unwrap().possible_values(),
+            );
+        }
+    }
+
+    #[test]
+    fn clock_is_only_read_under_a_time_limit() {
+        let (variables, arcs, _) = all_different(&[0, 1], &['a', 'b']);
+        let mut rng = simple_rng("clock");
+        let solver = Solver::new(
+            variables.clone(),
+            &arcs,
+            &Pigeons {},
+            &mut rng,
+            &SearchOptions::default(),
+        );
+        assert!(solver.started.is_none());
+
+        let timed = SearchOptions {
+            limits: Limits {
+                time: Some(Duration::from_secs(1)),
+                ..Limits::default()
+            },
+            ..SearchOptions::default()
+        };
+        let mut rng = simple_rng("clock");
+        let solver = Solver::new(variables, &arcs, &Pigeons {}, &mut rng, &timed);
+        assert!(solver.started.is_some());
+    }
+
+    #[test]
+    fn solver_resumes_past_solutions() {
+        let (variables, arcs, _) = all_different(&[0, 1, 2], &['a', 'b', 'c']);
+        let mut rng = simple_rng("resume");
+        let mut solver = Solver::new(
+            variables,
+            &arcs,
+            &Pigeons {},
+            &mut rng,
+            &SearchOptions::default(),
+        );
+
+        let mut solutions = 0;
+        while solver.run() == Solution::Consistent {
+            solutions += 1;
+        }
+        assert_eq!(solutions, 6);
+        assert_eq!(solver.step(), Some(Solution::NoSolution));
+    }
+
+    #[test]
+    fn backjumping_skips_unrelated_decisions() {
+        // Three unconstrained variables get decided before the pigeons, since they sort first.
+        let build = || {
+            let TestCase {
+                arcs,
+                mut variables,
+            } = build_pigeons();
+            for id in -3..0 {
+                variables.add_var(id, vec!['a', 'b', 'c']).unwrap();
+            }
+            TestCase { arcs, variables }
+        };
+
+        let mut rng = simple_rng("backjumping");
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build();
+        let (solution, chronological) = search(
+            &mut variables,
+            &arcs,
+            &Pigeons {},
+            &mut rng,
+            &SearchOptions::default(),
+        );
+        assert_eq!(solution, Solution::NoSolution);
+
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build();
+        let options = SearchOptions {
+            backjumping: true,
+            ..SearchOptions::default()
+        };
+        let (solution, backjumping) =
+            search(&mut variables, &arcs, &Pigeons {}, &mut rng, &options);
+        assert_eq!(solution, Solution::NoSolution);
+        assert!(
+            backjumping.backtracks * 10 < chronological.backtracks,
+            "{backjumping:?} vs {chronological:?}"
+        );
+    }
+
+    #[test]
+    fn backjumping_still_finds_every_solution() {
+        let count = |backjumping| {
+            let TestCase {
+                arcs,
+                mut variables,
+            } = build_test_case();
+            for id in 0..6 {
+                let var = variables.find_id(id).unwrap();
+                variables.update_var(var, vec!['a', 'b', 'c']);
+            }
+            let mut rng = simple_rng("every solution");
+            let options = SearchOptions {
+                backjumping,
+                ..SearchOptions::default()
+            };
+            let mut solver =
+                Solver::new(variables, &arcs, &StaticConstraints {}, &mut rng, &options);
+            let mut solutions = 0;
+            while solver.run() == Solution::Consistent {
+                solutions += 1;
+            }
+            solutions
+        };
+
+        // The even identifiers share one of 3 values, the odd ones take the other two.
+        assert_eq!(count(false), 6);
+        assert_eq!(count(true), 6);
+    }
+
+    #[test]
+    fn restarts_start_over_from_the_root() {
+        let TestCase { arcs, variables } = build_pigeons();
+        let mut rng = simple_rng("restarts");
+        let options = SearchOptions {
+            restarts: Some(RestartPolicy::Fixed(1)),
+            limits: Limits {
+                nodes: Some(50),
+                ..Limits::default()
+            },
+            ..SearchOptions::default()
+        };
+        let mut recorder = Recorder::default();
+
+        let mut solver = Solver::new(variables, &arcs, &Pigeons {}, &mut rng, &options)
+            .with_observer(&mut recorder);
+        // A single backtrack is never enough to exhaust the pigeons, so this only stops at the
+        // limit.
+        assert_eq!(solver.run(), Solution::LimitReached(Limit::Nodes));
+        let statistics = solver.statistics();
+        assert!(statistics.restarts > 10);
+        assert_eq!(statistics.restarts, statistics.backtracks);
+        drop(solver);
+        assert_eq!(recorder.restarts as u64, statistics.restarts);
+    }
+
+    #[test]
+    fn restarts_stop_once_solutions_are_found() {
+        let mut variables = VariableProvider::default();
+        for id in 0..2 {
+            variables.add_var(id, vec!['a', 'b', 'c']).unwrap();
+        }
+        let mut rng = simple_rng("restarts after solutions");
+        let options = SearchOptions {
+            restarts: Some(RestartPolicy::Fixed(1)),
+            ..SearchOptions::default()
+        };
+
+        let mut solver = Solver::new(variables, &[], &Pigeons {}, &mut rng, &options);
+        let mut solutions = HashSet::new();
+        let mut found = 0;
+        while solver.run() == Solution::Consistent {
+            found += 1;
+            solutions.insert(
+                (0..2)
+                    .map(|id| solver.variables().find_var(id).unwrap().possible_values()[0])
+                    .collect::<String>(),
+            );
+        }
+        assert_eq!((found, solutions.len()), (9, 9));
+        assert_eq!(solver.statistics().restarts, 0);
+    }
+
+    #[test]
+    fn growing_restarts_still_prove_no_solution() {
+        for policy in [
+            RestartPolicy::Luby(1),
+            RestartPolicy::Geometric {
+                first: 1,
+                factor: 2.0,
+            },
+        ] {
+            let TestCase {
+                arcs,
+                mut variables,
+            } = build_pigeons();
+            let mut rng = simple_rng("growing restarts");
+            let options = SearchOptions {
+                restarts: Some(policy),
+                variable_ordering: VariableOrdering::DomWdeg,
+                ..SearchOptions::default()
+            };
+            let (solution, statistics) =
+                search(&mut variables, &arcs, &Pigeons {}, &mut rng, &options);
+            assert_eq!(solution, Solution::NoSolution);
+            assert!(statistics.restarts > 0);
+        }
+    }
+
+    #[test]
+    fn nogoods_let_restarts_prove_no_solution() {
+        let TestCase { arcs, variables } = build_pigeons();
+        let mut rng = simple_rng("nogoods");
+        let options = SearchOptions {
+            // Without nogoods this never gets past the first backtrack, see
+            // `restarts_start_over_from_the_root`.
+            restarts: Some(RestartPolicy::Fixed(1)),
+            nogoods: Some(NogoodPolicy::default()),
+            limits: Limits {
+                nodes: Some(1000),
+                ..Limits::default()
+            },
+            ..SearchOptions::default()
+        };
+
+        let mut solver = Solver::new(variables, &arcs, &Pigeons {}, &mut rng, &options);
+        assert_eq!(solver.run(), Solution::NoSolution);
+        let statistics = solver.statistics();
+        assert!(statistics.restarts > 0);
+        assert!(statistics.nogoods > 0);
+        assert!(!solver.nogoods().is_empty());
+        assert!(solver.nogoods().len() as u64 <= statistics.nogoods);
+    }
+
+    #[test]
+    fn nogoods_keep_every_solution() {
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build_test_case();
+        for id in 0..6 {
+            let var = variables.find_id(id).unwrap();
+            variables.update_var(var, vec!['a', 'b', 'c']);
+        }
+        let mut rng = simple_rng("nogood solutions");
+        let options = SearchOptions {
+            nogoods: Some(NogoodPolicy::default()),
+            ..SearchOptions::default()
+        };
+
+        let mut solver = Solver::new(variables, &arcs, &StaticConstraints {}, &mut rng, &options);
+        let mut solutions = 0;
+        while solver.run() == Solution::Consistent {
+            solutions += 1;
+        }
+        assert_eq!(solutions, 6);
+    }
+
+    #[test]
+    fn every_propagation_level_agrees() {
+        let solve = |propagation| {
+            let TestCase {
+                arcs,
+                mut variables,
+            } = build_test_case();
+            for id in 0..6 {
+                let var = variables.find_id(id).unwrap();
+                variables.update_var(var, vec!['a', 'b', 'c']);
+            }
+            let mut rng = simple_rng("propagation");
+            let options = SearchOptions {
+                propagation,
+                ..SearchOptions::default()
+            };
+            let mut solver =
+                Solver::new(variables, &arcs, &StaticConstraints {}, &mut rng, &options);
+            let mut solutions = 0;
+            while solver.run() == Solution::Consistent {
+                solutions += 1;
+            }
+
+            let TestCase {
+                arcs,
+                mut variables,
+            } = build_pigeons();
+            let (solution, statistics) =
+                search(&mut variables, &arcs, &Pigeons {}, &mut rng, &options);
+            assert_eq!(solution, Solution::NoSolution);
+            (solutions, statistics.decisions)
+        };
+
+        let (none, none_decisions) = solve(Propagation::None);
+        let (fc, fc_decisions) = solve(Propagation::ForwardChecking);
+        let (mac, mac_decisions) = solve(Propagation::Mac);
+        assert_eq!((none, fc, mac), (6, 6, 6));
+        // Weaker propagation has to try more values to notice the pigeons don't fit.
+        assert!(none_decisions > fc_decisions);
+        assert!(fc_decisions >= mac_decisions);
+    }
+
+    #[test]
+    fn limited_discrepancy_search_widens_until_exhausted() {
+        let TestCase { arcs, variables } = build_test_case();
+        let mut rng = simple_rng("lds");
+        let options = SearchOptions {
+            strategy: Strategy::LimitedDiscrepancy,
+            value_ordering: ValueOrdering::First,
+            ..SearchOptions::default()
+        };
+        let mut solver = Solver::new(variables, &arcs, &StaticConstraints {}, &mut rng, &options);
+        assert_eq!(solver.run(), Solution::Consistent);
+        // Propagation alone makes every first choice work out.
+        assert_eq!(solver.discrepancy_limit(), Some(0));
+        let solution = (0..6)
+            .map(|id| solver.variables().find_var(id).unwrap().possible_values()[0])
+            .collect::<String>();
+        assert_eq!(solution, "abacab");
+
+        let TestCase { arcs, variables } = build_pigeons();
+        let mut solver = Solver::new(variables, &arcs, &Pigeons {}, &mut rng, &options);
+        assert_eq!(solver.run(), Solution::NoSolution);
+        assert!(solver.discrepancy_limit().unwrap() > 0);
+    }
+
+    #[test]
+    fn limited_discrepancy_search_finds_every_solution_with_learning() {
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build_test_case();
+        for id in 0..6 {
+            let var = variables.find_id(id).unwrap();
+            variables.update_var(var, vec!['a', 'b', 'c']);
+        }
+        let mut rng = simple_rng("lds learning");
+        let options = SearchOptions {
+            strategy: Strategy::LimitedDiscrepancy,
+            value_ordering: ValueOrdering::First,
+            backjumping: true,
+            nogoods: Some(NogoodPolicy::default()),
+            ..SearchOptions::default()
+        };
+        let mut solver = Solver::new(variables, &arcs, &StaticConstraints {}, &mut rng, &options);
+        let mut solutions = HashSet::new();
+        while solver.run() == Solution::Consistent {
+            solutions.insert(
+                (0..6)
+                    .map(|id| solver.variables().find_var(id).unwrap().possible_values()[0])
+                    .collect::<String>(),
+            );
+        }
+        assert_eq!(solutions.len(), 6);
+    }
+
+    #[test]
+    fn dom_wdeg_prefers_heavily_weighted_variables() {
+        let TestCase { arcs, variables } = build_test_case();
+        let mut weights = Weights::new(&arcs);
+        // Every domain is the same size, so the middle row wins with three neighbours each.
+        assert_eq!(weights.select(&variables), variables.find_id(2));
+
+        let (v5, v6) = (variables.find_id(4).unwrap(), variables.find_id(5).unwrap());
+        weights.bump((v6, v5));
+        weights.bump((v6, v5));
+        assert_eq!(weights.weighted_degree(v5), 6);
+        assert_eq!(weights.select(&variables), variables.find_id(2));
+        weights.bump((v6, v5));
+        assert_eq!(weights.select(&variables), Some(v5));
+    }
+
+    #[test]
+    fn search_stops_at_limits() {
+        let cases = [
+            (
+                Limits {
+                    nodes: Some(2),
+                    ..Limits::default()
+                },
+                Limit::Nodes,
+            ),
+            (
+                Limits {
+                    backtracks: Some(1),
+                    ..Limits::default()
+                },
+                Limit::Backtracks,
+            ),
+            (
+                Limits {
+                    time: Some(Duration::ZERO),
+                    ..Limits::default()
+                },
+                Limit::Time,
+            ),
+        ];
+
+        for (limits, expected) in cases {
+            let mut rng = simple_rng("limits");
+            let TestCase {
+                arcs,
+                mut variables,
+            } = build_pigeons();
+            let options = SearchOptions {
+                limits,
+                ..SearchOptions::default()
+            };
+            assert_eq!(
+                Solution::LimitReached(expected),
+                search(&mut variables, &arcs, &Pigeons {}, &mut rng, &options).0
+            );
+        }
+    }
+
+    #[test]
+    fn search_stops_when_cancelled() {
+        let mut rng = simple_rng("cancelled");
+        let TestCase {
+            arcs,
+            mut variables,
+        } = build_test_case();
+        let token = CancellationToken::new();
+        token.cancel();
+        let options = SearchOptions {
+            cancellation: Some(token),
+            ..SearchOptions::default()
+        };
+
+        assert_eq!(
+            Solution::LimitReached(Limit::Cancelled),
+            search(
+                &mut variables,
+                &arcs,
+                &StaticConstraints {},
+                &mut rng,
+                &options
+            )
+            .0
+        );
+    }
+
     /*
     #[test]
     fn backtrack_inconsistent() {
@@ -0,0 +1,433 @@
+//! Build up a [`ConstraintProvider`] from smaller ones, e.g. the standard relations in
+//! [`crate::constraints`].
+//!
+//! Each provider only takes part on the arcs it [`ConstraintProvider::constrains`], and is
+//! ignored elsewhere rather than trusted to accept every pair of values there.
+//!
+//! Combining two providers on the same arc loses their fast revision, since a value needs a
+//! single supporting value satisfying the combination, which revising each part on its own does
+//! not guarantee. On arcs only one of them constrains, its own revision is used. Revising an arc
+//! decides which providers apply once, so the pairs checked along the way are not counted in
+//! [`crate::statistics::Statistics::checks`].
+
+use std::collections::HashMap;
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType, retain, retain_by};
+use crate::variable_provider::{Variable, VariableID};
+
+/// Neighbours listed by either provider, if both list them.
+fn union<T>(a: Option<Vec<T>>, b: Option<Vec<T>>) -> Option<Vec<T>> {
+    let mut a = a?;
+    a.extend(b?);
+    Some(a)
+}
+
+/// Both providers must be satisfied, on the arcs each relates. Relates the arcs either of them
+/// does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<D, K, A, B> ConstraintProvider<D, K> for And<A, B>
+where
+    D: DomainType,
+    K: IdentifierType,
+    A: ConstraintProvider<D, K>,
+    B: ConstraintProvider<D, K>,
+{
+    fn check(&self, a: &Variable<D, K>, av: &D, b: &Variable<D, K>, bv: &D) -> bool {
+        (!self.0.constrains(a, b) || self.0.check(a, av, b, bv))
+            && (!self.1.constrains(a, b) || self.1.check(a, av, b, bv))
+    }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        self.0.constrains(a, b) || self.1.constrains(a, b)
+    }
+
+    fn neighbours(&self, a: &Variable<D, K>) -> Option<Vec<K>> {
+        union(self.0.neighbours(a), self.1.neighbours(a))
+    }
+
+    fn neighbour_ids(&self, a: &Variable<D, K>) -> Option<Vec<VariableID>> {
+        union(self.0.neighbour_ids(a), self.1.neighbour_ids(a))
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        Some(match (self.0.constrains(a, b), self.1.constrains(a, b)) {
+            (true, true) => retain_by(a, b, |av, bv| {
+                self.0.check(a, av, b, bv) && self.1.check(a, av, b, bv)
+            }),
+            (true, false) => retain(a, b, &self.0),
+            (false, true) => retain(a, b, &self.1),
+            (false, false) => vec![],
+        })
+    }
+}
+
+/// Either provider must be satisfied, among those relating the arc. Relates the arcs either of
+/// them does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<D, K, A, B> ConstraintProvider<D, K> for Or<A, B>
+where
+    D: DomainType,
+    K: IdentifierType,
+    A: ConstraintProvider<D, K>,
+    B: ConstraintProvider<D, K>,
+{
+    fn check(&self, a: &Variable<D, K>, av: &D, b: &Variable<D, K>, bv: &D) -> bool {
+        match (self.0.constrains(a, b), self.1.constrains(a, b)) {
+            (true, true) => self.0.check(a, av, b, bv) || self.1.check(a, av, b, bv),
+            (true, false) => self.0.check(a, av, b, bv),
+            (false, true) => self.1.check(a, av, b, bv),
+            (false, false) => true,
+        }
+    }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        self.0.constrains(a, b) || self.1.constrains(a, b)
+    }
+
+    fn neighbours(&self, a: &Variable<D, K>) -> Option<Vec<K>> {
+        union(self.0.neighbours(a), self.1.neighbours(a))
+    }
+
+    fn neighbour_ids(&self, a: &Variable<D, K>) -> Option<Vec<VariableID>> {
+        union(self.0.neighbour_ids(a), self.1.neighbour_ids(a))
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        Some(match (self.0.constrains(a, b), self.1.constrains(a, b)) {
+            (true, true) => retain_by(a, b, |av, bv| {
+                self.0.check(a, av, b, bv) || self.1.check(a, av, b, bv)
+            }),
+            (true, false) => retain(a, b, &self.0),
+            (false, true) => retain(a, b, &self.1),
+            (false, false) => vec![],
+        })
+    }
+}
+
+/// The provider must not be satisfied, on the arcs it relates. Other pairs of variables are
+/// unconstrained.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Not<A>(pub A);
+
+impl<D, K, A> ConstraintProvider<D, K> for Not<A>
+where
+    D: DomainType,
+    K: IdentifierType,
+    A: ConstraintProvider<D, K>,
+{
+    fn check(&self, a: &Variable<D, K>, av: &D, b: &Variable<D, K>, bv: &D) -> bool {
+        !self.0.constrains(a, b) || !self.0.check(a, av, b, bv)
+    }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        self.0.constrains(a, b)
+    }
+
+    fn neighbours(&self, a: &Variable<D, K>) -> Option<Vec<K>> {
+        self.0.neighbours(a)
+    }
+
+    fn neighbour_ids(&self, a: &Variable<D, K>) -> Option<Vec<VariableID>> {
+        self.0.neighbour_ids(a)
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        if self.0.constrains(a, b) {
+            Some(retain_by(a, b, |av, bv| !self.0.check(a, av, b, bv)))
+        } else {
+            Some(vec![])
+        }
+    }
+}
+
+/// A different provider for each arc. Arcs without one are unconstrained, like with the
+/// [`HashMap`] provider of [`crate::ac3::Constraint`]s.
+pub struct PerArc<D, K> {
+    routes: HashMap<(VariableID, VariableID), Box<dyn ConstraintProvider<D, K>>>,
+    /// The second variable of every routed arc, by its first.
+    neighbours: HashMap<VariableID, Vec<VariableID>>,
+}
+
+impl<D, K> Default for PerArc<D, K> {
+    fn default() -> Self {
+        Self {
+            routes: HashMap::new(),
+            neighbours: HashMap::new(),
+        }
+    }
+}
+
+impl<D, K> PerArc<D, K>
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constrain the arc from `a` to `b` with `provider`, replacing any provider it had.
+    pub fn route(
+        &mut self,
+        a: VariableID,
+        b: VariableID,
+        provider: impl ConstraintProvider<D, K> + 'static,
+    ) -> &mut Self {
+        if self.routes.insert((a, b), Box::new(provider)).is_none() {
+            self.neighbours.entry(a).or_default().push(b);
+        }
+        self
+    }
+}
+
+impl<D, K> ConstraintProvider<D, K> for PerArc<D, K>
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    fn check(&self, a: &Variable<D, K>, av: &D, b: &Variable<D, K>, bv: &D) -> bool {
+        self.routes
+            .get(&(a.index, b.index))
+            .is_none_or(|provider| provider.check(a, av, b, bv))
+    }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        self.routes
+            .get(&(a.index, b.index))
+            .is_some_and(|provider| provider.constrains(a, b))
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        self.routes
+            .get(&(a.index, b.index))
+            .and_then(|provider| provider.revise(a, b))
+    }
+
+    fn neighbour_ids(&self, a: &Variable<D, K>) -> Option<Vec<VariableID>> {
+        Some(self.neighbours.get(&a.index).cloned().unwrap_or_default())
+    }
+}
+
+/// The provider only applies to pairs of variables `filter` accepts, e.g. based on their
+/// identifiers. Other pairs are unconstrained.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct When<F, A> {
+    pub filter: F,
+    pub inner: A,
+}
+
+impl<D, K, F, A> ConstraintProvider<D, K> for When<F, A>
+where
+    D: DomainType,
+    K: IdentifierType,
+    F: Fn(&Variable<D, K>, &Variable<D, K>) -> bool,
+    A: ConstraintProvider<D, K>,
+{
+    fn check(&self, a: &Variable<D, K>, av: &D, b: &Variable<D, K>, bv: &D) -> bool {
+        !(self.filter)(a, b) || self.inner.check(a, av, b, bv)
+    }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        (self.filter)(a, b) && self.inner.constrains(a, b)
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        if (self.filter)(a, b) {
+            self.inner.revise(a, b)
+        } else {
+            None
+        }
+    }
+
+    fn neighbours(&self, a: &Variable<D, K>) -> Option<Vec<K>> {
+        self.inner.neighbours(a)
+    }
+
+    fn neighbour_ids(&self, a: &Variable<D, K>) -> Option<Vec<VariableID>> {
+        self.inner.neighbour_ids(a)
+    }
+}
+
+/// The first provider on the arcs it relates, the second everywhere else.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Fallback<A, B>(pub A, pub B);
+
+impl<D, K, A, B> ConstraintProvider<D, K> for Fallback<A, B>
+where
+    D: DomainType,
+    K: IdentifierType,
+    A: ConstraintProvider<D, K>,
+    B: ConstraintProvider<D, K>,
+{
+    fn check(&self, a: &Variable<D, K>, av: &D, b: &Variable<D, K>, bv: &D) -> bool {
+        if self.0.constrains(a, b) {
+            self.0.check(a, av, b, bv)
+        } else {
+            self.1.check(a, av, b, bv)
+        }
+    }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        self.0.constrains(a, b) || self.1.constrains(a, b)
+    }
+
+    fn neighbours(&self, a: &Variable<D, K>) -> Option<Vec<K>> {
+        union(self.0.neighbours(a), self.1.neighbours(a))
+    }
+
+    fn neighbour_ids(&self, a: &Variable<D, K>) -> Option<Vec<VariableID>> {
+        union(self.0.neighbour_ids(a), self.1.neighbour_ids(a))
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        if self.0.constrains(a, b) {
+            Some(retain(a, b, &self.0))
+        } else {
+            Some(retain(a, b, &self.1))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    use crate::{
+        ac3::{ConstraintProvider, ac3, arcs, new_constraint},
+        constraints::{Eq, Le, Lt, Ne},
+        test_util::{domain, revise},
+        variable_provider::{Variable, VariableProvider},
+    };
+
+    use super::{And, Fallback, Not, Or, PerArc, When};
+
+    #[test]
+    fn logical_combinators() {
+        let all = || vec![0, 1, 2, 3];
+        // Eq and Ne each support 1 and 2 on their own, but never with the same value of b.
+        assert_eq!(revise(&And(Eq, Ne), &all(), &[1, 2]), vec![]);
+        assert_eq!(
+            revise(&And(Le, Ne), &all(), &[2]),
+            revise(&Lt, &all(), &[2])
+        );
+        assert_eq!(revise(&Or(Lt, Eq), &all(), &[2]), revise(&Le, &all(), &[2]));
+        assert_eq!(revise(&Not(Eq), &all(), &[2]), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn operands_only_apply_to_their_own_arcs() {
+        let mut variables = VariableProvider::from([
+            ('a', vec![0, 1, 2, 3]),
+            ('b', vec![0, 1, 2, 3]),
+            ('c', vec![1]),
+            ('d', vec![1, 2]),
+        ]);
+        let [a, b, c, d] = ['a', 'b', 'c', 'd'].map(|id| variables.find_id(id).unwrap());
+        let less = || HashMap::from([((a, b), new_constraint(|x: &u32, y: &u32| x < y))]);
+        let differ = || HashMap::from([((c, d), new_constraint(|x: &u32, y: &u32| x != y))]);
+        let equal = || HashMap::from([((d, c), new_constraint(|x: &u32, y: &u32| x == y))]);
+
+        // `differ` accepts everything on (a, b), which must not excuse `less` there.
+        let constraints = Or(less(), differ());
+        let related = arcs(&variables, &constraints);
+        ac3(&mut variables, &related, &constraints);
+        assert_eq!(domain(&variables, 'a'), vec![0, 1, 2]);
+
+        // Negating `equal` must not forbid everything on (c, d), where it does not apply.
+        let constraints = And(Not(equal()), differ());
+        let related = arcs(&variables, &constraints);
+        ac3(&mut variables, &related, &constraints);
+        assert_eq!(domain(&variables, 'd'), vec![2]);
+        assert_eq!(domain(&variables, 'c'), vec![1]);
+    }
+
+    #[test]
+    fn routes_are_listed_as_neighbours() {
+        let mut variables = VariableProvider::default();
+        let ids = (0..100)
+            .map(|id| variables.add_var(id, vec![0, 1]).unwrap())
+            .collect::<Vec<_>>();
+        let mut routes = PerArc::new();
+        for pair in ids.windows(2) {
+            routes
+                .route(pair[0], pair[1], Lt)
+                .route(pair[1], pair[0], Not(Le));
+        }
+        routes.route(ids[0], ids[1], Ne);
+
+        let first = variables.get_var(ids[0]).unwrap();
+        assert_eq!(routes.neighbour_ids(first), Some(vec![ids[1]]));
+        let constraints = And(routes, PerArc::new());
+        assert_eq!(arcs(&variables, &constraints).len(), 198);
+    }
+
+    #[test]
+    fn routing_combinators() {
+        let mut variables = VariableProvider::from([
+            ('a', vec![0, 1, 2, 3]),
+            ('b', vec![0, 1, 2, 3]),
+            ('c', vec![2]),
+        ]);
+        let [a, b] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+
+        // a < b on its own arc, everything else differs, but c is only related to b.
+        let mut routes = PerArc::new();
+        routes.route(a, b, Lt);
+        let constraints = Fallback(
+            routes,
+            When {
+                filter: |x: &Variable<u32, char>, y: &Variable<u32, char>| {
+                    x.identifier != 'a' && y.identifier != 'a'
+                },
+                inner: Ne,
+            },
+        );
+        let arcs = arcs(&variables, &constraints);
+        assert_eq!(arcs.len(), 3);
+        ac3(&mut variables, &arcs, &constraints);
+
+        assert_eq!(domain(&variables, 'a'), vec![0, 1, 2]);
+        assert_eq!(domain(&variables, 'b'), vec![0, 1, 3]);
+        assert_eq!(domain(&variables, 'c'), vec![2]);
+    }
+
+    /// Relates pairs where `a` is the first variable, counting how often it is asked.
+    struct FromA(Cell<u64>);
+
+    impl ConstraintProvider<u32, char> for FromA {
+        fn check(
+            &self,
+            _a: &Variable<u32, char>,
+            av: &u32,
+            _b: &Variable<u32, char>,
+            bv: &u32,
+        ) -> bool {
+            av < bv
+        }
+
+        fn constrains(&self, a: &Variable<u32, char>, _b: &Variable<u32, char>) -> bool {
+            self.0.set(self.0.get() + 1);
+            a.identifier == 'a'
+        }
+    }
+
+    #[test]
+    fn fallback_decides_once_per_arc() {
+        let mut variables =
+            VariableProvider::from([('a', (0..50).collect()), ('b', (0..50).collect())]);
+        let [a, b] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+
+        let constraints = Fallback(FromA(Cell::new(0)), Not(Le));
+        let statistics = ac3(&mut variables, &[(a, b), (b, a)], &constraints);
+        // Once per revision, rather than once per pair of values.
+        assert_eq!(constraints.0.0.get(), statistics.revisions);
+        assert_eq!(domain(&variables, 'a'), (0..49).collect::<Vec<_>>());
+        assert_eq!(domain(&variables, 'b'), (1..50).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ac3::{Constraint, DomainType, IdentifierType};
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Builds the [`HashMap`] [`crate::ac3::ConstraintProvider`] one relation at a time, so each
+/// relation only has to be written once rather than once per direction.
+pub struct ConstraintBuilder<D> {
+    constraints: HashMap<(VariableID, VariableID), Constraint<D>>,
+    arcs: Vec<(VariableID, VariableID)>,
+}
+
+impl<D> Default for ConstraintBuilder<D> {
+    fn default() -> Self {
+        Self {
+            constraints: HashMap::new(),
+            arcs: vec![],
+        }
+    }
+}
+
+impl<D> ConstraintBuilder<D>
+where
+    D: DomainType + 'static,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Relate `a` to `b`, `relation` receiving `a`'s value first. The reverse direction is
+    /// derived by swapping the arguments, and both arcs are added.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `a` and `b` are already related.
+    pub fn relate(
+        &mut self,
+        a: VariableID,
+        b: VariableID,
+        relation: impl Fn(&D, &D) -> bool + 'static,
+    ) -> Result<&mut Self, String> {
+        let forward = Rc::new(relation);
+        let backward = Rc::clone(&forward);
+        self.insert(
+            a,
+            b,
+            Box::new(move |x, y| forward(x, y)),
+            Box::new(move |y, x| backward(x, y)),
+        )
+    }
+
+    /// Relate `a` to `b` with both directions given explicitly, e.g. when the reverse has a
+    /// cheaper form. Every pair of values in `variables` is checked to make sure `forward` and
+    /// `backward` describe the same relation.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `a` and `b` are already related, if either is missing from `variables`, or if
+    /// the directions disagree on some pair of values.
+    pub fn relate_both<K>(
+        &mut self,
+        variables: &VariableProvider<D, K>,
+        a: VariableID,
+        b: VariableID,
+        forward: impl Fn(&D, &D) -> bool + 'static,
+        backward: impl Fn(&D, &D) -> bool + 'static,
+    ) -> Result<&mut Self, String>
+    where
+        K: IdentifierType,
+    {
+        let (Some(a_var), Some(b_var)) = (variables.get_var(a), variables.get_var(b)) else {
+            return Err(format!("Unknown variable in {a:?}, {b:?}"));
+        };
+        for x in a_var.possible_values().iter() {
+            for y in b_var.possible_values().iter() {
+                if forward(x, y) != backward(y, x) {
+                    return Err(format!(
+                        "Directions disagree between {} = {x} and {} = {y}",
+                        a_var.identifier, b_var.identifier
+                    ));
+                }
+            }
+        }
+        self.insert(a, b, Box::new(forward), Box::new(backward))
+    }
+
+    /// Arcs for every relation so far, both directions of each.
+    #[must_use]
+    pub fn arcs(&self) -> &[(VariableID, VariableID)] {
+        &self.arcs
+    }
+
+    /// The finished constraints.
+    #[must_use]
+    pub fn build(self) -> HashMap<(VariableID, VariableID), Constraint<D>> {
+        self.constraints
+    }
+
+    fn insert(
+        &mut self,
+        a: VariableID,
+        b: VariableID,
+        forward: Constraint<D>,
+        backward: Constraint<D>,
+    ) -> Result<&mut Self, String> {
+        if self.constraints.contains_key(&(a, b)) || self.constraints.contains_key(&(b, a)) {
+            return Err(format!("Already related {a:?}, {b:?}"));
+        }
+        self.constraints.insert((a, b), forward);
+        self.constraints.insert((b, a), backward);
+        self.arcs.extend([(a, b), (b, a)]);
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ac3::ac3, variable_provider::VariableProvider};
+
+    use super::ConstraintBuilder;
+
+    #[test]
+    fn relations_hold_both_ways() {
+        let mut variables =
+            VariableProvider::from([('a', vec![1, 2, 3]), ('b', vec![1, 2, 3]), ('c', vec![1])]);
+        let [a, b, c] = ['a', 'b', 'c'].map(|id| variables.find_id(id).unwrap());
+
+        let mut builder = ConstraintBuilder::new();
+        builder
+            .relate(a, b, |x, y| x < y)
+            .unwrap()
+            .relate(b, c, |x, y| x != y)
+            .unwrap();
+        assert!(builder.relate(b, a, |x, y| x == y).is_err());
+        assert_eq!(builder.arcs(), &[(a, b), (b, a), (b, c), (c, b)]);
+
+        let arcs = builder.arcs().to_vec();
+        let constraints = builder.build();
+        ac3(&mut variables, &arcs, &constraints);
+        assert_eq!(*variables.get_var(a).unwrap().possible_values(), vec![1, 2]);
+        assert_eq!(*variables.get_var(b).unwrap().possible_values(), vec![2, 3]);
+    }
+
+    #[test]
+    fn mismatched_directions_are_rejected() {
+        let variables = VariableProvider::from([('a', vec![1, 2, 3]), ('b', vec![1, 2, 3])]);
+        let [a, b] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+
+        let mut builder = ConstraintBuilder::new();
+        // Forgot to flip the reverse direction.
+        assert!(
+            builder
+                .relate_both(&variables, a, b, |x, y| x < y, |x, y| x < y)
+                .is_err()
+        );
+        assert!(builder.arcs().is_empty());
+        assert!(
+            builder
+                .relate_both(&variables, a, b, |x, y| x < y, |y, x| x < y)
+                .is_ok()
+        );
+        assert_eq!(builder.arcs().len(), 2);
+    }
+}
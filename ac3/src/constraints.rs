@@ -0,0 +1,353 @@
+//! Common relations between values, ready to use as a [`ConstraintProvider`]. Each applies to
+//! whichever arcs it is consulted on, with the first variable of the arc on the left, e.g. [`Lt`]
+//! on the arc `(a, b)` means `a < b`, so the reverse arc `(b, a)` needs a provider of its own.
+//!
+//! None of them has a scope of its own: [`ConstraintProvider::constrains`] accepts every pair of
+//! variables. Deriving arcs from one with [`crate::ac3::arcs`] therefore relates every pair both
+//! ways with the same relation, which for [`Lt`] has no solution at all. Scope them first with
+//! [`crate::combinators::PerArc`] or [`crate::combinators::When`].
+//!
+//! Where the shape of the relation allows, arcs are revised without checking every pair of
+//! values, which the [`crate::statistics::Statistics::checks`] counter reflects.
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use crate::variable_provider::Variable;
+
+/// Arithmetic that [`Offset`] and [`AbsDiff`] need, reporting overflow instead of panicking or
+/// wrapping around. Implemented for the primitive integers.
+pub trait Arithmetic: DomainType + Ord {
+    /// `self + other`, or `None` if that overflows.
+    fn checked_add(self, other: Self) -> Option<Self>;
+    /// `self - other`, or `None` if that overflows.
+    fn checked_sub(self, other: Self) -> Option<Self>;
+}
+
+/// Whether `x` and `y` are at most `limit` apart. A distance too large for `D` is larger than
+/// any `limit`.
+fn within<D>(x: D, y: D, limit: D) -> bool
+where
+    D: Arithmetic,
+{
+    let (low, high) = if x < y { (x, y) } else { (y, x) };
+    high.checked_sub(low)
+        .is_some_and(|distance| distance <= limit)
+}
+
+/// Remove the values of `a` that fail `keep`, returning them.
+fn remove_unless<D, K, F>(a: &Variable<D, K>, mut keep: F) -> Vec<D>
+where
+    D: DomainType,
+    F: FnMut(&D) -> bool,
+{
+    let mut removed = vec![];
+    a.retain(|value| {
+        let kept = keep(value);
+        if !kept {
+            removed.push(*value);
+        }
+        kept
+    });
+    removed
+}
+
+/// The values are equal.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Eq;
+
+impl<D, K> ConstraintProvider<D, K> for Eq
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    fn check(&self, _a: &Variable<D, K>, av: &D, _b: &Variable<D, K>, bv: &D) -> bool {
+        av == bv
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        let b_values = b.possible_values();
+        Some(remove_unless(a, |value| b_values.contains(value)))
+    }
+}
+
+/// The values differ.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Ne;
+
+impl<D, K> ConstraintProvider<D, K> for Ne
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    fn check(&self, _a: &Variable<D, K>, av: &D, _b: &Variable<D, K>, bv: &D) -> bool {
+        av != bv
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        // Two or more values in `b` support anything.
+        let b_values = b.possible_values();
+        Some(match b_values.as_slice() {
+            [] => remove_unless(a, |_| false),
+            [only] => remove_unless(a, |value| value != only),
+            _ => vec![],
+        })
+    }
+}
+
+/// The first value is less than the second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Lt;
+
+impl<D, K> ConstraintProvider<D, K> for Lt
+where
+    D: DomainType + PartialOrd,
+    K: IdentifierType,
+{
+    fn check(&self, _a: &Variable<D, K>, av: &D, _b: &Variable<D, K>, bv: &D) -> bool {
+        av < bv
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        let max = largest(&b.possible_values());
+        Some(remove_unless(a, |value| {
+            max.is_some_and(|max| *value < max)
+        }))
+    }
+}
+
+/// The first value is less than or equal to the second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Le;
+
+impl<D, K> ConstraintProvider<D, K> for Le
+where
+    D: DomainType + PartialOrd,
+    K: IdentifierType,
+{
+    fn check(&self, _a: &Variable<D, K>, av: &D, _b: &Variable<D, K>, bv: &D) -> bool {
+        av <= bv
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        let max = largest(&b.possible_values());
+        Some(remove_unless(a, |value| {
+            max.is_some_and(|max| *value <= max)
+        }))
+    }
+}
+
+fn largest<D>(values: &[D]) -> Option<D>
+where
+    D: DomainType + PartialOrd,
+{
+    values
+        .iter()
+        .copied()
+        .reduce(|max, value| if value > max { value } else { max })
+}
+
+/// The second value is the first plus the given offset. A first value whose sum would overflow
+/// has no second value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Offset<D>(pub D);
+
+impl<D, K> ConstraintProvider<D, K> for Offset<D>
+where
+    D: Arithmetic,
+    K: IdentifierType,
+{
+    fn check(&self, _a: &Variable<D, K>, av: &D, _b: &Variable<D, K>, bv: &D) -> bool {
+        av.checked_add(self.0) == Some(*bv)
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        let b_values = b.possible_values();
+        Some(remove_unless(a, |value| {
+            value
+                .checked_add(self.0)
+                .is_some_and(|sum| b_values.contains(&sum))
+        }))
+    }
+}
+
+/// The values differ by at most the given amount, in either direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AbsDiff<D>(pub D);
+
+impl<D, K> ConstraintProvider<D, K> for AbsDiff<D>
+where
+    D: Arithmetic,
+    K: IdentifierType,
+{
+    fn check(&self, _a: &Variable<D, K>, av: &D, _b: &Variable<D, K>, bv: &D) -> bool {
+        within(*av, *bv, self.0)
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        let mut b_values = b.possible_values().clone();
+        b_values.sort_unstable();
+        Some(remove_unless(a, |value| {
+            // Only the closest values of `b` on either side can be near enough.
+            let above = b_values.partition_point(|y| y < value);
+            let below = above.checked_sub(1).and_then(|index| b_values.get(index));
+            b_values
+                .get(above)
+                .into_iter()
+                .chain(below)
+                .any(|y| within(*value, *y, self.0))
+        }))
+    }
+}
+
+/// Only the listed pairs of values are allowed, first value first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InSet<D>(pub Vec<(D, D)>);
+
+impl<D, K> ConstraintProvider<D, K> for InSet<D>
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    fn check(&self, _a: &Variable<D, K>, av: &D, _b: &Variable<D, K>, bv: &D) -> bool {
+        self.0.contains(&(*av, *bv))
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        let b_values = b.possible_values();
+        Some(remove_unless(a, |value| {
+            self.0
+                .iter()
+                .any(|(x, y)| x == value && b_values.contains(y))
+        }))
+    }
+}
+
+/// If the first value is one of `when`, the second must be one of `then`. Other values are
+/// unconstrained.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Implies<D> {
+    pub when: Vec<D>,
+    pub then: Vec<D>,
+}
+
+impl<D, K> ConstraintProvider<D, K> for Implies<D>
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    fn check(&self, _a: &Variable<D, K>, av: &D, _b: &Variable<D, K>, bv: &D) -> bool {
+        !self.when.contains(av) || self.then.contains(bv)
+    }
+
+    fn revise(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> Option<Vec<D>> {
+        let b_values = b.possible_values();
+        if b_values.is_empty() {
+            return Some(remove_unless(a, |_| false));
+        }
+        // Whether `when` values are supported doesn't depend on which one it is.
+        let then = b_values.iter().any(|value| self.then.contains(value));
+        Some(remove_unless(a, |value| then || !self.when.contains(value)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ac3::{ConstraintProvider, ac3, arcs},
+        combinators::{Fallback, Not, When},
+        test_util::revise,
+        variable_provider::{Variable, VariableProvider},
+    };
+
+    use super::{AbsDiff, Eq, Implies, InSet, Le, Lt, Ne, Offset};
+
+    #[test]
+    fn standard_constraints_revise_like_a_scan() {
+        let all = || vec![0, 1, 2, 3, 4];
+
+        assert_eq!(revise(&Eq, &all(), &[1, 3, 7]), vec![1, 3]);
+        assert_eq!(revise(&Ne, &all(), &[2]), vec![0, 1, 3, 4]);
+        assert_eq!(revise(&Ne, &all(), &[2, 3]), all());
+        assert_eq!(revise(&Ne, &all(), &[]), vec![]);
+        assert_eq!(revise(&Lt, &all(), &[1, 3]), vec![0, 1, 2]);
+        assert_eq!(revise(&Le, &all(), &[1, 3]), vec![0, 1, 2, 3]);
+        assert_eq!(revise(&Lt, &all(), &[]), vec![]);
+        assert_eq!(revise(&Offset(2), &all(), &[2, 3, 9]), vec![0, 1]);
+        assert_eq!(revise(&AbsDiff(1), &all(), &[0, 4]), vec![0, 1, 3, 4]);
+        assert_eq!(revise(&AbsDiff(0), &all(), &[4, 1, 9]), vec![1, 4]);
+        assert_eq!(revise(&AbsDiff(2), &all(), &[]), vec![]);
+        assert_eq!(
+            revise(&InSet(vec![(0, 1), (2, 2), (3, 1)]), &all(), &[1]),
+            vec![0, 3]
+        );
+
+        let walls = Implies {
+            when: vec![0, 1],
+            then: vec![4],
+        };
+        assert_eq!(revise(&walls, &all(), &[2, 3]), vec![2, 3, 4]);
+        assert_eq!(revise(&walls, &all(), &[3, 4]), all());
+    }
+
+    #[test]
+    fn bounds_revision_skips_checks() {
+        let mut variables =
+            VariableProvider::from([('a', (0..100).collect()), ('b', (0..100).collect())]);
+        let [a, b] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+        let statistics = ac3(&mut variables, &[(a, b)], &Lt);
+        assert_eq!(statistics.checks, 0);
+        assert_eq!(variables.get_var(a).unwrap().possible_values().len(), 99);
+
+        let statistics = ac3(&mut variables, &[(a, b)], &AbsDiff(3));
+        assert_eq!(statistics.checks, 0);
+    }
+
+    #[test]
+    fn arithmetic_never_overflows() {
+        let mut variables =
+            VariableProvider::from([('a', vec![254, u8::MAX]), ('b', vec![u8::MAX])]);
+        let [a, b] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+        ac3(&mut variables, &[(a, b)], &Offset(1u8));
+        assert_eq!(*variables.get_var(a).unwrap().possible_values(), vec![254]);
+
+        let mut variables =
+            VariableProvider::from([('a', vec![i8::MIN, 0, i8::MAX]), ('b', vec![i8::MIN])]);
+        let [a, b] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+        let (a_var, b_var) = (variables.get_var(a).unwrap(), variables.get_var(b).unwrap());
+        assert!(!AbsDiff(i8::MAX).check(a_var, &i8::MAX, b_var, &i8::MIN));
+        assert!(AbsDiff(i8::MAX).check(a_var, &-1, b_var, &i8::MIN));
+        ac3(&mut variables, &[(a, b)], &AbsDiff(i8::MAX));
+        assert_eq!(
+            *variables.get_var(a).unwrap().possible_values(),
+            vec![i8::MIN]
+        );
+    }
+
+    #[test]
+    fn derived_arcs_need_a_scope() {
+        let mut variables = VariableProvider::from([
+            ('a', vec![1, 2, 3]),
+            ('b', vec![1, 2, 3]),
+            ('c', vec![1, 2, 3]),
+        ]);
+
+        // a < b < c: Lt from earlier to later identifiers, and the reverse on the way back.
+        let ordered = Fallback(
+            When {
+                filter: |x: &Variable<u32, char>, y: &Variable<u32, char>| {
+                    x.identifier < y.identifier
+                },
+                inner: Lt,
+            },
+            Not(Le),
+        );
+        let arcs = arcs(&variables, &ordered);
+        assert_eq!(arcs.len(), 6);
+        ac3(&mut variables, &arcs, &ordered);
+        for (id, value) in [('a', 1), ('b', 2), ('c', 3)] {
+            assert_eq!(
+                *variables.find_var(id).unwrap().possible_values(),
+                vec![value]
+            );
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use crate::observer::SearchObserver;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Why a value was removed from a variable's possible values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Cause<D> {
+    /// It was already missing when tracking started, e.g. because of a user pin.
+    Pinned,
+    /// No value of `neighbour` supported it, `neighbour_values` being the neighbour's possible
+    /// values at the time.
+    Arc {
+        neighbour: VariableID,
+        neighbour_values: Vec<D>,
+    },
+    /// The search decided on another value for the variable.
+    Decision,
+    /// A learned nogood ruled it out, see [`crate::nogood`].
+    Nogood,
+}
+
+/// A value removed from a variable, and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Removal<D> {
+    pub variable: VariableID,
+    pub value: D,
+    pub cause: Cause<D>,
+    /// Number of search decisions in effect at the time.
+    pub level: usize,
+}
+
+/// Records why each value was removed during [`crate::ac3::ac3_observed`] or
+/// [`crate::backtrack::search_observed`], for debugging constraints and tilesets. Removals undone
+/// by backtracking are forgotten again.
+#[derive(Clone, Debug)]
+pub struct Explanations<D> {
+    /// Domains before any pins.
+    original: HashMap<VariableID, Vec<D>>,
+    removals: Vec<Removal<D>>,
+    /// Variables decided on, innermost last.
+    decisions: Vec<VariableID>,
+}
+
+impl<D> Explanations<D>
+where
+    D: DomainType,
+{
+    /// Start tracking from `variables`. Values in `original` but missing from `variables` are
+    /// recorded as [`Cause::Pinned`], so `original` should hold the domains before any pins were
+    /// placed, or be `variables` itself if there are none.
+    #[must_use]
+    pub fn new<K>(original: &VariableProvider<D, K>, variables: &VariableProvider<D, K>) -> Self
+    where
+        K: IdentifierType,
+    {
+        let mut removals = vec![];
+        for var in original.iter() {
+            let Some(current) = variables.get_var(var.index) else {
+                continue;
+            };
+            for value in var.possible_values().iter() {
+                if !current.possible_values().contains(value) {
+                    removals.push(Removal {
+                        variable: var.index,
+                        value: *value,
+                        cause: Cause::Pinned,
+                        level: 0,
+                    });
+                }
+            }
+        }
+
+        Self {
+            original: original
+                .iter()
+                .map(|v| (v.index, v.possible_values().clone()))
+                .collect(),
+            removals,
+            decisions: vec![],
+        }
+    }
+
+    /// Every removal still in effect, oldest first.
+    #[must_use]
+    pub fn removals(&self) -> &[Removal<D>] {
+        &self.removals
+    }
+
+    /// Why `value` is no longer possible for `variable`, or `None` if it still is.
+    #[must_use]
+    pub fn why(&self, variable: VariableID, value: &D) -> Option<&Removal<D>> {
+        self.removals
+            .iter()
+            .find(|r| r.variable == variable && r.value == *value)
+    }
+
+    /// The removal of `value` from `variable`, followed by the removals that led to it, back to
+    /// pins and decisions. For a [`Cause::Arc`] removal, that means the removals of every
+    /// neighbour value that would have supported it.
+    #[must_use]
+    pub fn explain<K, CP>(
+        &self,
+        variables: &VariableProvider<D, K>,
+        constraints: &CP,
+        variable: VariableID,
+        value: &D,
+    ) -> Vec<&Removal<D>>
+    where
+        K: IdentifierType,
+        CP: ConstraintProvider<D, K>,
+    {
+        let mut chain: Vec<&Removal<D>> = vec![];
+        let mut queue = VecDeque::from([(variable, *value)]);
+
+        while let Some((variable, value)) = queue.pop_front() {
+            let Some(removal) = self.why(variable, &value) else {
+                continue;
+            };
+            if chain.iter().any(|r| std::ptr::eq(*r, removal)) {
+                continue;
+            }
+            chain.push(removal);
+
+            if let Cause::Arc {
+                neighbour,
+                neighbour_values,
+            } = &removal.cause
+                && let Some(x) = variables.get_var(variable)
+                && let Some(y) = variables.get_var(*neighbour)
+            {
+                let supports = self
+                    .original
+                    .get(neighbour)
+                    .into_iter()
+                    .flatten()
+                    .filter(|n| {
+                        !neighbour_values.contains(n) && constraints.check(x, &value, y, n)
+                    });
+                queue.extend(supports.map(|n| (*neighbour, *n)));
+            }
+        }
+        chain
+    }
+
+    fn record(&mut self, variable: VariableID, value: D, cause: Cause<D>) {
+        if self.why(variable, &value).is_none() {
+            self.removals.push(Removal {
+                variable,
+                value,
+                cause,
+                level: self.decisions.len(),
+            });
+        }
+    }
+
+    /// Forget everything removed after the first `level` decisions.
+    fn undo_to(&mut self, level: usize) {
+        self.decisions.truncate(level);
+        self.removals.retain(|r| r.level <= level);
+    }
+}
+
+impl<D, K> SearchObserver<D, K> for Explanations<D>
+where
+    D: DomainType,
+{
+    fn on_decision(&mut self, variable: VariableID, value: &D) {
+        self.decisions.push(variable);
+        let others = self
+            .original
+            .get(&variable)
+            .into_iter()
+            .flatten()
+            .filter(|v| *v != value)
+            .copied()
+            .collect::<Vec<_>>();
+        for other in others {
+            self.record(variable, other, Cause::Decision);
+        }
+    }
+
+    fn on_revision(
+        &mut self,
+        variable: VariableID,
+        removed: &[D],
+        neighbour: VariableID,
+        neighbour_values: &[D],
+    ) {
+        for value in removed {
+            self.record(
+                variable,
+                *value,
+                Cause::Arc {
+                    neighbour,
+                    neighbour_values: neighbour_values.to_vec(),
+                },
+            );
+        }
+    }
+
+    fn on_reduction(&mut self, variable: VariableID, removed: &[D]) {
+        // Anything not already explained by `on_revision` came from a nogood.
+        for value in removed {
+            self.record(variable, *value, Cause::Nogood);
+        }
+    }
+
+    fn on_backtrack(&mut self, variable: VariableID) {
+        if let Some(level) = self.decisions.iter().rposition(|v| *v == variable) {
+            self.undo_to(level);
+        }
+    }
+
+    fn on_restart(&mut self) {
+        self.undo_to(0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        ac3::{ac3_observed, new_constraint},
+        backtrack::{SearchOptions, Solution, search_observed},
+        test_util::{ordered, simple_rng},
+        variable_provider::VariableProvider,
+    };
+
+    use super::{Cause, Explanations};
+
+    #[test]
+    fn explanations_walk_back_to_pins() {
+        // a < b < c, with c pinned to 3.
+        let (original, arcs, constraints) = ordered();
+        let [b, c] = ['b', 'c'].map(|id| original.find_id(id).unwrap());
+        let mut variables = original.clone();
+        variables.update_var(c, vec![3]);
+
+        let mut explanations = Explanations::new(&original, &variables);
+        let _ = ac3_observed(&mut variables, &arcs, &constraints, &mut explanations);
+
+        assert_eq!(explanations.why(c, &4).unwrap().cause, Cause::Pinned);
+        assert_eq!(
+            explanations.why(b, &3).unwrap().cause,
+            Cause::Arc {
+                neighbour: c,
+                neighbour_values: vec![3]
+            }
+        );
+        assert!(explanations.why(b, &2).is_none());
+
+        // b = 3 needed c = 4, which was pinned away.
+        let chain = explanations
+            .explain(&variables, &constraints, b, &3)
+            .into_iter()
+            .map(|r| (r.variable, r.value))
+            .collect::<Vec<_>>();
+        assert_eq!(chain, vec![(b, 3), (c, 4)]);
+    }
+
+    #[test]
+    fn explanations_follow_the_search() {
+        let mut rng = simple_rng("explanations");
+        let mut variables = VariableProvider::from([('a', vec![1, 2, 3]), ('b', vec![1, 2, 3])]);
+        let [a, b] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+        let arcs = [(a, b), (b, a)];
+        let constraints = HashMap::from([
+            ((a, b), new_constraint(|x: &i32, y: &i32| x != y)),
+            ((b, a), new_constraint(|x: &i32, y: &i32| x != y)),
+        ]);
+
+        let mut explanations = Explanations::new(&variables, &variables);
+        let (solution, _) = search_observed(
+            &mut variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &SearchOptions::default(),
+            &mut explanations,
+        );
+        assert_eq!(solution, Solution::Consistent);
+
+        // Every value that is gone is explained, and nothing else is.
+        for (variable, values) in [(a, [1, 2, 3]), (b, [1, 2, 3])] {
+            for value in values {
+                let possible = variables
+                    .get_var(variable)
+                    .unwrap()
+                    .possible_values()
+                    .contains(&value);
+                assert_eq!(explanations.why(variable, &value).is_none(), possible);
+            }
+        }
+        let decided = explanations
+            .removals()
+            .iter()
+            .filter(|r| r.cause == Cause::Decision)
+            .count();
+        // Two values of the first variable decided, then the last of the second.
+        assert_eq!(decided, 3);
+    }
+}
@@ -1,8 +1,12 @@
 use std::collections::HashMap;
+use std::fmt::Debug;
 use std::hash::BuildHasher;
 
-use crate::ac3::{Constraint, ConstraintProvider, DomainType, IdentifierType};
-use crate::variable_provider::{Variable, VariableID};
+use crate::ac3::{Constraint, ConstraintProvider, DomainType, IdentifierType, SyncConstraint};
+use crate::constraints::Arithmetic;
+use crate::observer::SearchObserver;
+use crate::optimize::Objective;
+use crate::variable_provider::{Variable, VariableID, VariableProvider};
 
 impl DomainType for char {}
 impl DomainType for u8 {}
@@ -26,6 +30,22 @@ impl IdentifierType for i32 {}
 impl IdentifierType for i64 {}
 impl IdentifierType for usize {}
 
+macro_rules! arithmetic {
+    ($($t:ty),*) => {
+        $(impl Arithmetic for $t {
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$t>::checked_add(self, other)
+            }
+
+            fn checked_sub(self, other: Self) -> Option<Self> {
+                <$t>::checked_sub(self, other)
+            }
+        })*
+    };
+}
+
+arithmetic!(u8, u16, u32, u64, i8, i16, i32, i64, usize);
+
 // TODO: A constraint database could probably avoid the hash structure by scanning relationships
 // more efficient... maybe tied directly to arc list?
 impl<K, D, S1> ConstraintProvider<D, K> for HashMap<(VariableID, VariableID), Constraint<D>, S1>
@@ -39,4 +59,80 @@ where
         self.get(&(a.index, b.index))
             .is_none_or(|checker: &Constraint<D>| checker(av, bv))
     }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        self.contains_key(&(a.index, b.index))
+    }
+}
+
+impl<K, D, S1> ConstraintProvider<D, K> for HashMap<(VariableID, VariableID), SyncConstraint<D>, S1>
+where
+    S1: BuildHasher,
+    D: DomainType,
+    K: IdentifierType,
+{
+    fn check(&self, a: &Variable<D, K>, av: &D, b: &Variable<D, K>, bv: &D) -> bool {
+        self.get(&(a.index, b.index))
+            .is_none_or(|checker| checker(av, bv))
+    }
+
+    fn constrains(&self, a: &Variable<D, K>, b: &Variable<D, K>) -> bool {
+        self.contains_key(&(a.index, b.index))
+    }
+}
+
+/// Plain functions can act as an [`Objective`] without any bound estimate.
+impl<D, K, C, F> Objective<D, K> for F
+where
+    F: Fn(&VariableProvider<D, K>) -> C,
+    C: Ord + Copy + Debug,
+{
+    type Cost = C;
+
+    fn cost(&self, variables: &VariableProvider<D, K>) -> C {
+        self(variables)
+    }
+}
+
+/// The unit type observes nothing.
+impl<D, K> SearchObserver<D, K> for () {}
+
+/// Lets an observer be lent to a search and inspected afterwards.
+impl<D, K, O> SearchObserver<D, K> for &mut O
+where
+    O: SearchObserver<D, K> + ?Sized,
+{
+    fn on_decision(&mut self, variable: VariableID, value: &D) {
+        (**self).on_decision(variable, value);
+    }
+
+    fn on_revision(
+        &mut self,
+        variable: VariableID,
+        removed: &[D],
+        neighbour: VariableID,
+        neighbour_values: &[D],
+    ) {
+        (**self).on_revision(variable, removed, neighbour, neighbour_values);
+    }
+
+    fn on_reduction(&mut self, variable: VariableID, removed: &[D]) {
+        (**self).on_reduction(variable, removed);
+    }
+
+    fn on_wipeout(&mut self, variable: VariableID) {
+        (**self).on_wipeout(variable);
+    }
+
+    fn on_backtrack(&mut self, variable: VariableID) {
+        (**self).on_backtrack(variable);
+    }
+
+    fn on_restart(&mut self) {
+        (**self).on_restart();
+    }
+
+    fn on_solution(&mut self, variables: &VariableProvider<D, K>) {
+        (**self).on_solution(variables);
+    }
 }
@@ -4,6 +4,27 @@
 #![forbid(clippy::mod_module_files)]
 
 pub mod ac3;
+pub mod assumptions;
 pub mod backtrack;
+pub mod combinators;
+pub mod constraint_builder;
+pub mod constraints;
+pub mod explain;
 mod impls;
+pub mod limits;
+pub mod local_search;
+pub mod nogood;
+pub mod observer;
+pub mod optimize;
+#[cfg(feature = "parallel")]
+pub mod portfolio;
+pub mod problem;
+pub mod regenerate;
+pub mod repair;
+pub mod restart;
+pub mod sample;
+pub mod statistics;
+#[cfg(test)]
+mod test_util;
+pub mod unsat_core;
 pub mod variable_provider;
@@ -0,0 +1,92 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+/// Bounds on how much work a search may do before giving up. Unset limits are unbounded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of decisions, i.e. values chosen for a variable.
+    pub nodes: Option<u64>,
+    /// Maximum number of times the search may undo a decision.
+    pub backtracks: Option<u64>,
+    /// Maximum wall-clock time.
+    pub time: Option<Duration>,
+}
+
+/// Identifies why a search stopped early.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    Nodes,
+    Backtracks,
+    Time,
+    Cancelled,
+}
+
+/// A cheaply clonable flag for stopping a search from elsewhere, such as another thread. The
+/// search checks it between steps, so it stops shortly after [`CancellationToken::cancel`] rather
+/// than immediately.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    /// Tokens whose cancellation this one follows.
+    parents: Vec<CancellationToken>,
+}
+
+impl CancellationToken {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that is also cancelled once any of `parents` is. Cancelling it leaves the parents
+    /// alone.
+    #[must_use]
+    pub fn child_of<'a>(parents: impl IntoIterator<Item = &'a CancellationToken>) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            parents: parents.into_iter().cloned().collect(),
+        }
+    }
+
+    /// Ask every search holding a clone of this token, or of a child of it, to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self.parents.iter().any(CancellationToken::is_cancelled)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CancellationToken;
+
+    #[test]
+    fn cancel_is_shared_between_clones() {
+        let token = CancellationToken::new();
+        let other = token.clone();
+        assert!(!other.is_cancelled());
+        token.cancel();
+        assert!(other.is_cancelled());
+    }
+
+    #[test]
+    fn children_follow_their_parents() {
+        let (first, second) = (CancellationToken::new(), CancellationToken::new());
+        let child = CancellationToken::child_of([&first, &second]);
+        child.cancel();
+        assert!(!first.is_cancelled() && !second.is_cancelled());
+
+        let child = CancellationToken::child_of([&first, &second]);
+        second.cancel();
+        assert!(child.is_cancelled());
+        assert!(!first.is_cancelled());
+    }
+}
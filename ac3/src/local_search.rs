@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+
+use rand::seq::IndexedRandom;
+use rand::{Rng, RngExt};
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType, ac3};
+use crate::backtrack::Solution;
+use crate::limits::Limit;
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Tuning for [`min_conflicts`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LocalSearchOptions {
+    /// Maximum number of times a variable is reassigned before giving up.
+    pub steps: u64,
+    /// Chance (between 0 and 1) of reassigning a conflicted variable to a random value instead of
+    /// its least conflicting one, which helps escape local minima. Values outside that range are
+    /// clamped into it, and NaN counts as 0.
+    pub noise: f64,
+    /// Run [`ac3`] first, so the starting assignment only uses arc consistent values. Skipped if
+    /// it empties a domain.
+    pub prune: bool,
+}
+
+impl Default for LocalSearchOptions {
+    fn default() -> Self {
+        Self {
+            steps: 10_000,
+            noise: 0.05,
+            prune: true,
+        }
+    }
+}
+
+impl LocalSearchOptions {
+    /// [`LocalSearchOptions::noise`], as a valid probability.
+    fn noise(&self) -> f64 {
+        if self.noise.is_nan() {
+            0.0
+        } else {
+            self.noise.clamp(0.0, 1.0)
+        }
+    }
+}
+
+/// Result of [`min_conflicts`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct MinConflicts {
+    /// [`Solution::Consistent`] if no constraint is violated, [`Solution::NoSolution`] if a
+    /// variable has no possible values at all, and [`Limit::Nodes`] if the steps ran out first.
+    pub solution: Solution,
+    /// Arcs whose constraint the returned assignment violates.
+    pub violated: Vec<(VariableID, VariableID)>,
+    pub statistics: Statistics,
+}
+
+/// Local search for an assignment that satisfies every constraint, for problems too large to
+/// search exhaustively. Starts from a random assignment and repeatedly moves a variable involved
+/// in a violated constraint to the value that violates the fewest constraints.
+///
+/// Unless a variable has no possible values, `variables` is left with exactly one value per
+/// variable: the assignment with the fewest violated arcs seen. Finding none does not prove there
+/// is no solution.
+#[must_use]
+pub fn min_conflicts<K, D, CP, R>(
+    variables: &mut VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    options: &LocalSearchOptions,
+) -> MinConflicts
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    let mut statistics = Statistics::default();
+    if options.prune {
+        let mut pruned = variables.clone();
+        statistics += ac3(&mut pruned, arcs, constraints);
+        if pruned.is_consistent() {
+            *variables = pruned;
+        }
+    }
+
+    // Values each variable may move between, fixed for the whole search.
+    let domains = variables
+        .iter()
+        .map(|v| (v.index, v.possible_values().clone()))
+        .collect::<HashMap<_, _>>();
+    if domains.values().any(Vec::is_empty) {
+        return MinConflicts {
+            solution: Solution::NoSolution,
+            violated: vec![],
+            statistics,
+        };
+    }
+
+    let mut neighbourhood: HashMap<VariableID, Vec<(VariableID, VariableID)>> = HashMap::new();
+    for &(x, y) in arcs {
+        neighbourhood.entry(x).or_default().push((x, y));
+        if x != y {
+            neighbourhood.entry(y).or_default().push((x, y));
+        }
+    }
+
+    for var in variables.iter() {
+        if let Some(value) = var.choose(rng) {
+            var.replace_possible_values(vec![value]);
+        }
+    }
+
+    let mut violated = arcs
+        .iter()
+        .filter(|arc| violates(variables, constraints, **arc, None, &mut statistics))
+        .copied()
+        .collect::<Vec<_>>();
+    let mut best = (violated.clone(), variables.clone());
+    let noise = options.noise();
+
+    for _ in 0..options.steps {
+        let Some(&(x, y)) = violated.choose(rng) else {
+            break;
+        };
+        let v = if rng.random_bool(0.5) { x } else { y };
+        let neighbours = &neighbourhood[&v];
+
+        let value = if rng.random_bool(noise) {
+            domains[&v].choose(rng).copied()
+        } else {
+            let scored = domains[&v]
+                .iter()
+                .map(|value| {
+                    let conflicts = neighbours
+                        .iter()
+                        .filter(|arc| {
+                            violates(
+                                variables,
+                                constraints,
+                                **arc,
+                                Some((v, value)),
+                                &mut statistics,
+                            )
+                        })
+                        .count();
+                    (conflicts, *value)
+                })
+                .collect::<Vec<_>>();
+            let fewest = scored.iter().map(|(conflicts, _)| *conflicts).min();
+            let ties = scored
+                .into_iter()
+                .filter(|(conflicts, _)| Some(*conflicts) == fewest)
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>();
+            ties.choose(rng).copied()
+        };
+
+        if let Some(value) = value {
+            variables.update_var(v, vec![value]);
+            statistics.decisions += 1;
+        }
+
+        // Only arcs touching `v` can have changed.
+        violated.retain(|(a, b)| *a != v && *b != v);
+        violated.extend(
+            neighbours
+                .iter()
+                .filter(|arc| violates(variables, constraints, **arc, None, &mut statistics)),
+        );
+        if violated.len() < best.0.len() {
+            best = (violated.clone(), variables.clone());
+        }
+    }
+
+    let (violated, best_variables) = best;
+    *variables = best_variables;
+    MinConflicts {
+        solution: if violated.is_empty() {
+            Solution::Consistent
+        } else {
+            Solution::LimitReached(Limit::Nodes)
+        },
+        violated,
+        statistics,
+    }
+}
+
+/// Whether the current assignment violates the constraint on `(x, y)`, optionally with one
+/// variable moved to another value.
+fn violates<K, D, CP>(
+    variables: &VariableProvider<D, K>,
+    constraints: &CP,
+    (x, y): (VariableID, VariableID),
+    moved: Option<(VariableID, &D)>,
+    statistics: &mut Statistics,
+) -> bool
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+{
+    let value = |id: VariableID| match moved {
+        Some((v, value)) if v == id => Some(*value),
+        _ => variables
+            .get_var(id)
+            .and_then(|var| var.possible_values().first().copied()),
+    };
+    if let Some(x_var) = variables.get_var(x)
+        && let Some(y_var) = variables.get_var(y)
+        && let Some(x_value) = value(x)
+        && let Some(y_value) = value(y)
+    {
+        statistics.checks += 1;
+        !constraints.check(x_var, &x_value, y_var, &y_value)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        backtrack::Solution,
+        limits::Limit,
+        test_util::{all_different, simple_rng},
+        variable_provider::VariableProvider,
+    };
+
+    use super::{LocalSearchOptions, min_conflicts};
+
+    #[test]
+    fn min_conflicts_repairs_to_a_solution() {
+        let mut rng = simple_rng("min conflicts");
+        let (mut variables, arcs, constraints) =
+            all_different(&['a', 'b', 'c', 'd', 'e'], &[1, 2, 3, 4, 5]);
+
+        let result = min_conflicts(
+            &mut variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &LocalSearchOptions::default(),
+        );
+
+        assert_eq!(result.solution, Solution::Consistent);
+        assert!(result.violated.is_empty());
+        let mut values = variables
+            .iter()
+            .map(|v| {
+                assert_eq!(v.possible_values().len(), 1);
+                v.possible_values()[0]
+            })
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn min_conflicts_returns_the_best_assignment_it_found() {
+        let mut rng = simple_rng("pigeons");
+        let (mut variables, arcs, constraints) = all_different(&['a', 'b', 'c', 'd'], &[1, 2, 3]);
+
+        let result = min_conflicts(
+            &mut variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &LocalSearchOptions {
+                steps: 100,
+                ..LocalSearchOptions::default()
+            },
+        );
+
+        // Two pigeons share a hole, which violates the arcs both ways between them.
+        assert_eq!(result.solution, Solution::LimitReached(Limit::Nodes));
+        assert_eq!(result.violated.len(), 2);
+        assert!(result.statistics.decisions <= 100);
+
+        for noise in [-1.0, 2.0, f64::NAN] {
+            let (mut variables, arcs, constraints) =
+                all_different(&['a', 'b', 'c', 'd'], &[1, 2, 3]);
+            let options = LocalSearchOptions {
+                steps: 10,
+                noise,
+                ..LocalSearchOptions::default()
+            };
+            let result = min_conflicts(&mut variables, &arcs, &constraints, &mut rng, &options);
+            assert_eq!(result.solution, Solution::LimitReached(Limit::Nodes));
+        }
+
+        let mut variables = VariableProvider::from([('a', vec![]), ('b', vec![1])]);
+        let result = min_conflicts(
+            &mut variables,
+            &[],
+            &constraints,
+            &mut rng,
+            &LocalSearchOptions::default(),
+        );
+        assert_eq!(result.solution, Solution::NoSolution);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::ac3::{DomainType, IdentifierType};
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// A combination of assignments the search found cannot be part of any solution.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Nogood<D> {
+    pub assignments: Vec<(VariableID, D)>,
+    /// How often this nogood pruned a value or a branch.
+    pub hits: u64,
+}
+
+/// Which nogood to forget when [`NogoodPolicy::capacity`] is reached.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NogoodDeletion {
+    /// The nogood learned first.
+    Oldest,
+    /// The nogood with the fewest [`Nogood::hits`], the oldest of those if tied.
+    #[default]
+    LeastUsed,
+    /// The nogood with the most assignments, which is the least likely to apply again.
+    Longest,
+}
+
+/// Bounds for nogood learning, see [`crate::backtrack::SearchOptions::nogoods`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NogoodPolicy {
+    /// Maximum number of nogoods kept at once.
+    pub capacity: usize,
+    pub deletion: NogoodDeletion,
+}
+
+impl Default for NogoodPolicy {
+    fn default() -> Self {
+        Self {
+            capacity: 1000,
+            deletion: NogoodDeletion::default(),
+        }
+    }
+}
+
+/// What checking the nogoods against the current domains found.
+#[derive(Debug, PartialEq)]
+pub(crate) enum NogoodCheck<D> {
+    /// Every other assignment of a nogood holds, so `value` must go from `variable`. `because`
+    /// lists the variables of those other assignments.
+    Prune {
+        variable: VariableID,
+        value: D,
+        because: Vec<VariableID>,
+    },
+    /// Every assignment of a nogood holds.
+    Violated { because: Vec<VariableID> },
+}
+
+/// The learned nogoods of a search, kept across backtracks and restarts.
+#[derive(Debug)]
+pub(crate) struct Nogoods<D> {
+    nogoods: Vec<Nogood<D>>,
+    policy: NogoodPolicy,
+}
+
+impl<D> Nogoods<D>
+where
+    D: DomainType,
+{
+    pub(crate) fn new(policy: NogoodPolicy) -> Self {
+        Self {
+            nogoods: vec![],
+            policy,
+        }
+    }
+
+    pub(crate) fn as_slice(&self) -> &[Nogood<D>] {
+        &self.nogoods
+    }
+
+    /// Remember that `assignments` cannot all hold at once. Returns `false` if it was already
+    /// known or there is no room for it.
+    pub(crate) fn learn(&mut self, assignments: Vec<(VariableID, D)>) -> bool {
+        if assignments.is_empty()
+            || self.policy.capacity == 0
+            || self.nogoods.iter().any(|n| n.assignments == assignments)
+        {
+            return false;
+        }
+
+        if self.nogoods.len() >= self.policy.capacity {
+            let forget = match self.policy.deletion {
+                NogoodDeletion::Oldest => Some(0),
+                NogoodDeletion::LeastUsed => self
+                    .nogoods
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, n)| n.hits)
+                    .map(|(index, _)| index),
+                NogoodDeletion::Longest => self
+                    .nogoods
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .max_by_key(|(_, n)| n.assignments.len())
+                    .map(|(index, _)| index),
+            };
+            if let Some(index) = forget {
+                self.nogoods.remove(index);
+            }
+        }
+
+        self.nogoods.push(Nogood {
+            assignments,
+            hits: 0,
+        });
+        true
+    }
+
+    /// Find the first nogood that is violated or can prune a value from `variables`.
+    pub(crate) fn check<K>(&mut self, variables: &VariableProvider<D, K>) -> Option<NogoodCheck<D>>
+    where
+        K: IdentifierType,
+    {
+        self.nogoods.iter_mut().find_map(|nogood| {
+            let mut open = None;
+            for (index, (variable, value)) in nogood.assignments.iter().enumerate() {
+                let var = variables.get_var(*variable)?;
+                let possible = var.possible_values();
+                if !possible.contains(value) {
+                    // This assignment can no longer hold, so neither can the nogood.
+                    return None;
+                } else if possible.len() > 1 {
+                    if open.is_some() {
+                        return None;
+                    }
+                    open = Some(index);
+                }
+            }
+
+            nogood.hits += 1;
+            let because = |skip: Option<usize>| {
+                nogood
+                    .assignments
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| Some(*index) != skip)
+                    .map(|(_, (variable, _))| *variable)
+                    .collect()
+            };
+            Some(match open {
+                Some(index) => NogoodCheck::Prune {
+                    variable: nogood.assignments[index].0,
+                    value: nogood.assignments[index].1,
+                    because: because(open),
+                },
+                None => NogoodCheck::Violated {
+                    because: because(None),
+                },
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::variable_provider::VariableProvider;
+
+    use super::{NogoodCheck, NogoodDeletion, NogoodPolicy, Nogoods};
+
+    #[test]
+    fn nogoods_prune_and_detect_violations() {
+        let mut variables = VariableProvider::from([('a', vec![1]), ('b', vec![1, 2])]);
+        let a = variables.find_id('a').unwrap();
+        let b = variables.find_id('b').unwrap();
+        let mut nogoods = Nogoods::new(NogoodPolicy::default());
+        assert!(nogoods.learn(vec![(a, 1), (b, 2)]));
+        assert!(!nogoods.learn(vec![(a, 1), (b, 2)]));
+
+        assert_eq!(
+            nogoods.check(&variables),
+            Some(NogoodCheck::Prune {
+                variable: b,
+                value: 2,
+                because: vec![a],
+            })
+        );
+
+        variables.update_var(b, vec![2]);
+        assert_eq!(
+            nogoods.check(&variables),
+            Some(NogoodCheck::Violated {
+                because: vec![a, b]
+            })
+        );
+
+        variables.update_var(a, vec![3]);
+        assert_eq!(nogoods.check(&variables), None);
+        assert_eq!(nogoods.as_slice()[0].hits, 2);
+    }
+
+    #[test]
+    fn nogoods_forget_by_policy() {
+        let variables = VariableProvider::from([('a', vec![1, 2, 3]), ('b', vec![1, 2, 3])]);
+        let a = variables.find_id('a').unwrap();
+        let b = variables.find_id('b').unwrap();
+        let learn_three = |deletion| {
+            let mut nogoods = Nogoods::new(NogoodPolicy {
+                capacity: 2,
+                deletion,
+            });
+            nogoods.learn(vec![(a, 1), (b, 1)]);
+            nogoods.learn(vec![(a, 2)]);
+            nogoods.nogoods[0].hits = 5;
+            nogoods.learn(vec![(a, 3)]);
+            nogoods
+                .as_slice()
+                .iter()
+                .map(|n| n.assignments.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            learn_three(NogoodDeletion::Oldest),
+            vec![vec![(a, 2)], vec![(a, 3)]]
+        );
+        assert_eq!(
+            learn_three(NogoodDeletion::LeastUsed),
+            vec![vec![(a, 1), (b, 1)], vec![(a, 3)]]
+        );
+        assert_eq!(
+            learn_three(NogoodDeletion::Longest),
+            vec![vec![(a, 2)], vec![(a, 3)]]
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Receives events as propagation and search progress, for example to trace a search or render a
+/// map filling in. Every method does nothing by default, so implementors only pick the events
+/// they care about.
+pub trait SearchObserver<D, K> {
+    /// The search chose `value` for `variable`.
+    fn on_decision(&mut self, _variable: VariableID, _value: &D) {}
+
+    /// Revising the arc from `variable` to `neighbour` removed `removed`, because none of
+    /// `neighbour_values`, the neighbour's possible values, supported them. Called right before
+    /// the matching [`SearchObserver::on_reduction`].
+    fn on_revision(
+        &mut self,
+        _variable: VariableID,
+        _removed: &[D],
+        _neighbour: VariableID,
+        _neighbour_values: &[D],
+    ) {
+    }
+
+    /// Propagation removed `removed` from the possible values of `variable`.
+    fn on_reduction(&mut self, _variable: VariableID, _removed: &[D]) {}
+
+    /// Propagation removed the last possible value of `variable`.
+    fn on_wipeout(&mut self, _variable: VariableID) {}
+
+    /// The search undid decisions back to the one made for `variable`, restoring every domain to
+    /// how it was before that decision. A new decision for `variable` follows.
+    fn on_backtrack(&mut self, _variable: VariableID) {}
+
+    /// The search abandoned every decision to start over from the initial domains.
+    fn on_restart(&mut self) {}
+
+    /// Every variable has exactly one possible value left.
+    fn on_solution(&mut self, _variables: &VariableProvider<D, K>) {}
+}
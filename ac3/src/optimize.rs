@@ -0,0 +1,248 @@
+use std::fmt::Debug;
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use crate::backtrack::{SearchOptions, Solution, Solver};
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+use rand::Rng;
+
+/// Scores assignments for [`optimize`]. Lower costs are better, so maximizing something means
+/// returning its negation (or wrapping it in [`std::cmp::Reverse`]).
+pub trait Objective<D, K> {
+    type Cost: Ord + Copy + Debug;
+
+    /// Cost of a complete assignment, one where every variable has exactly one possible value.
+    fn cost(&self, variables: &VariableProvider<D, K>) -> Self::Cost;
+
+    /// An optimistic estimate for a partial assignment: no way of completing `variables` may cost
+    /// less than the returned value. Branches whose bound cannot beat the best solution found so
+    /// far are skipped. Returning `None` (the default) disables pruning.
+    fn bound(&self, _variables: &VariableProvider<D, K>) -> Option<Self::Cost> {
+        None
+    }
+}
+
+/// Result of [`optimize`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Optimum<C> {
+    /// [`Solution::Consistent`] if any solution was found.
+    pub solution: Solution,
+    /// Cost of the best solution found.
+    pub cost: Option<C>,
+    /// Whether the whole search space was covered, meaning no better solution exists. This is
+    /// `false` if a limit stopped the search early.
+    pub proven: bool,
+    pub statistics: Statistics,
+}
+
+/// Branch and bound search for the solution with the lowest [`Objective::cost`].
+///
+/// On success `variables` holds the best assignment found. If `options` stops the search early,
+/// the best solution found so far is still returned, but it is not [`Optimum::proven`].
+#[must_use]
+pub fn optimize<K, D, CP, R, O>(
+    variables: &mut VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    objective: &O,
+    options: &SearchOptions,
+) -> Optimum<O::Cost>
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+    O: Objective<D, K>,
+{
+    let mut best: Option<(O::Cost, VariableProvider<D, K>)> = None;
+    let mut solver = Solver::new(std::mem::take(variables), arcs, constraints, rng, options);
+
+    let outcome = loop {
+        let incumbent = best.as_ref().map(|(cost, _)| *cost);
+        let solution = solver.next_solution(|partial| {
+            incumbent.is_some_and(|best_cost| {
+                objective
+                    .bound(partial)
+                    .is_some_and(|bound| bound >= best_cost)
+            })
+        });
+        if solution != Solution::Consistent {
+            break solution;
+        }
+
+        let cost = objective.cost(solver.variables());
+        if incumbent.is_none_or(|best_cost| cost < best_cost) {
+            best = Some((cost, solver.variables().clone()));
+        }
+    };
+
+    let proven = outcome == Solution::NoSolution;
+    let statistics = solver.statistics();
+    *variables = solver.into_variables();
+    match best {
+        Some((cost, best_variables)) => {
+            *variables = best_variables;
+            Optimum {
+                solution: Solution::Consistent,
+                cost: Some(cost),
+                proven,
+                statistics,
+            }
+        }
+        None => Optimum {
+            solution: outcome,
+            cost: None,
+            proven,
+            statistics,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        ac3::new_constraint,
+        backtrack::{SearchOptions, Solution},
+        limits::{Limit, Limits},
+        test_util::{all_different, simple_rng, value},
+        variable_provider::VariableProvider,
+    };
+
+    use super::{Objective, optimize};
+
+    /// Weighs `c` the heaviest, so the best solution puts the smallest value there.
+    struct Weighted;
+    impl Objective<i32, char> for Weighted {
+        type Cost = i32;
+
+        fn cost(&self, variables: &VariableProvider<i32, char>) -> i32 {
+            value(variables, 'a') + value(variables, 'b') * 10 + value(variables, 'c') * 100
+        }
+
+        fn bound(&self, variables: &VariableProvider<i32, char>) -> Option<i32> {
+            let min = |id| {
+                *variables
+                    .find_var(id)
+                    .unwrap()
+                    .possible_values()
+                    .iter()
+                    .min()
+                    .unwrap()
+            };
+            Some(min('a') + min('b') * 10 + min('c') * 100)
+        }
+    }
+
+    #[test]
+    fn optimize_finds_lowest_cost() {
+        let mut rng = simple_rng("optimize");
+        let (mut variables, arcs, constraints) = all_different(&['a', 'b', 'c'], &[1, 2, 3]);
+
+        let optimum = optimize(
+            &mut variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &Weighted,
+            &SearchOptions::default(),
+        );
+
+        assert_eq!(optimum.solution, Solution::Consistent);
+        assert_eq!(optimum.cost, Some(123));
+        assert!(optimum.proven);
+        assert_eq!(value(&variables, 'a'), 3);
+        assert_eq!(value(&variables, 'b'), 2);
+        assert_eq!(value(&variables, 'c'), 1);
+    }
+
+    #[test]
+    fn optimize_without_bound() {
+        let mut rng = simple_rng("no bound");
+        let (mut variables, arcs, constraints) = all_different(&['a', 'b', 'c'], &[1, 2, 3]);
+
+        let optimum = optimize(
+            &mut variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &|vars: &VariableProvider<i32, char>| -value(vars, 'a'),
+            &SearchOptions::default(),
+        );
+
+        assert_eq!(optimum.cost, Some(-3));
+        assert_eq!(value(&variables, 'a'), 3);
+    }
+
+    #[test]
+    fn optimize_reports_no_solution() {
+        let mut rng = simple_rng("none");
+        let mut variables = VariableProvider::from([('a', vec![1]), ('b', vec![1])]);
+        let a = variables.find_id('a').unwrap();
+        let b = variables.find_id('b').unwrap();
+        let constraints = HashMap::from([
+            ((a, b), new_constraint(|a: &i32, b: &i32| a != b)),
+            ((b, a), new_constraint(|a: &i32, b: &i32| a != b)),
+        ]);
+
+        let optimum = optimize(
+            &mut variables,
+            &[(a, b), (b, a)],
+            &constraints,
+            &mut rng,
+            &Weighted,
+            &SearchOptions::default(),
+        );
+
+        assert_eq!(optimum.solution, Solution::NoSolution);
+        assert_eq!(optimum.cost, None);
+        assert!(optimum.proven);
+    }
+
+    #[test]
+    fn optimize_is_unproven_when_limited() {
+        let mut rng = simple_rng("limited");
+        let (mut variables, arcs, constraints) = all_different(&['a', 'b', 'c'], &[1, 2, 3]);
+        let options = SearchOptions {
+            limits: Limits {
+                nodes: Some(2),
+                ..Limits::default()
+            },
+            ..SearchOptions::default()
+        };
+
+        let optimum = optimize(
+            &mut variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &Weighted,
+            &options,
+        );
+
+        assert_eq!(optimum.solution, Solution::Consistent);
+        assert!(optimum.cost.is_some());
+        assert!(!optimum.proven);
+
+        let mut variables = all_different(&['a', 'b', 'c'], &[1, 2, 3]).0;
+        let options = SearchOptions {
+            limits: Limits {
+                nodes: Some(0),
+                ..Limits::default()
+            },
+            ..SearchOptions::default()
+        };
+        let optimum = optimize(
+            &mut variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &Weighted,
+            &options,
+        );
+        assert_eq!(optimum.solution, Solution::LimitReached(Limit::Nodes));
+        assert!(!optimum.proven);
+    }
+}
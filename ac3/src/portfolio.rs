@@ -0,0 +1,275 @@
+use std::sync::mpsc;
+use std::thread;
+
+use rand::Rng;
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use crate::backtrack::{SearchOptions, Solution, search};
+use crate::limits::{CancellationToken, Limit};
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Result of [`portfolio`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Portfolio {
+    pub solution: Solution,
+    /// Index of the configuration that decided [`Portfolio::solution`], if any did.
+    pub winner: Option<usize>,
+    /// Work done by every configuration together.
+    pub statistics: Statistics,
+}
+
+/// Run [`search`] once per configuration, each on its own thread with its own random number
+/// generator and options, and keep whichever finishes first. A solution from one configuration is
+/// as good as any other, and so is proving there is none, so the rest are stopped at that point.
+/// Cancelling `cancellation` stops the whole portfolio, while a configuration's own
+/// [`SearchOptions::cancellation`] only stops that configuration. Neither token is cancelled by
+/// the portfolio itself, so both can be reused.
+///
+/// The constraints are shared between the threads, so they must be [`Sync`]. Providers holding
+/// plain [`crate::ac3::Constraint`]s or boxed providers, such as those built by
+/// [`crate::constraint_builder::ConstraintBuilder`], [`crate::problem::Problem`] or
+/// [`crate::combinators::PerArc`], are not; use a [`std::collections::HashMap`] of
+/// [`crate::ac3::SyncConstraint`]s instead.
+///
+/// On [`Solution::Consistent`] `variables` holds the winning assignment. If every configuration
+/// stops early, the limit reported is that of the first configuration not cancelled, or
+/// [`Limit::Cancelled`] if there is none.
+///
+/// # Errors
+///
+/// Fails if there are no configurations to run.
+pub fn portfolio<K, D, CP, R>(
+    variables: &mut VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    configurations: Vec<(R, SearchOptions)>,
+    cancellation: &CancellationToken,
+) -> Result<Portfolio, String>
+where
+    K: IdentifierType + Send,
+    D: DomainType + Send,
+    CP: ConstraintProvider<D, K> + Sync,
+    R: Rng + Send,
+{
+    if configurations.is_empty() {
+        return Err("No configurations to run".to_string());
+    }
+
+    let mut statistics = Statistics::default();
+    let mut winner = None;
+    let mut limits = vec![];
+    // Cancelled once a configuration wins, to stop the others.
+    let finished = CancellationToken::new();
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for (index, (mut rng, options)) in configurations.into_iter().enumerate() {
+            let sender = sender.clone();
+            let mut variables = variables.clone();
+            let cancellation = CancellationToken::child_of(
+                [&finished, cancellation]
+                    .into_iter()
+                    .chain(options.cancellation.as_ref()),
+            );
+            let options = SearchOptions {
+                cancellation: Some(cancellation),
+                ..options
+            };
+            scope.spawn(move || {
+                let (solution, statistics) =
+                    search(&mut variables, arcs, constraints, &mut rng, &options);
+                // The receiver outlives every thread, so this cannot fail.
+                let _ = sender.send((index, solution, statistics, variables));
+            });
+        }
+        drop(sender);
+
+        for (index, solution, worker_statistics, worker_variables) in receiver {
+            statistics += worker_statistics;
+            match solution {
+                Solution::LimitReached(limit) => limits.push((index, limit)),
+                _ if winner.is_some() => {}
+                _ => {
+                    finished.cancel();
+                    winner = Some((index, solution));
+                    if solution == Solution::Consistent {
+                        *variables = worker_variables;
+                    }
+                }
+            }
+        }
+    });
+
+    limits.sort_unstable_by_key(|(index, _)| *index);
+    let solution = match winner {
+        Some((_, solution)) => solution,
+        None => Solution::LimitReached(
+            limits
+                .iter()
+                .map(|(_, limit)| *limit)
+                .find(|limit| *limit != Limit::Cancelled)
+                .unwrap_or(Limit::Cancelled),
+        ),
+    };
+
+    Ok(Portfolio {
+        solution,
+        winner: winner.map(|(index, _)| index),
+        statistics,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use crate::{
+        ac3::{SyncConstraint, new_sync_constraint},
+        backtrack::{SearchOptions, Solution, VariableOrdering},
+        limits::{CancellationToken, Limit, Limits},
+        restart::RestartPolicy,
+        test_util::all_different,
+        variable_provider::VariableID,
+    };
+
+    use super::portfolio;
+
+    /// Every arc's variables must differ, in a provider that can be shared between threads.
+    fn differ(
+        arcs: &[(VariableID, VariableID)],
+    ) -> HashMap<(VariableID, VariableID), SyncConstraint<char>> {
+        arcs.iter()
+            .map(|arc| (*arc, new_sync_constraint(|a: &char, b: &char| a != b)))
+            .collect()
+    }
+
+    fn configurations() -> Vec<(StdRng, SearchOptions)> {
+        vec![
+            (StdRng::seed_from_u64(1), SearchOptions::default()),
+            (
+                StdRng::seed_from_u64(2),
+                SearchOptions {
+                    variable_ordering: VariableOrdering::DomWdeg,
+                    restarts: Some(RestartPolicy::Luby(4)),
+                    ..SearchOptions::default()
+                },
+            ),
+            (
+                StdRng::seed_from_u64(3),
+                SearchOptions {
+                    backjumping: true,
+                    ..SearchOptions::default()
+                },
+            ),
+        ]
+    }
+
+    #[test]
+    fn portfolio_keeps_the_first_answer() {
+        let (mut variables, arcs, _) = all_different(&[0, 1, 2], &['a', 'b', 'c']);
+        let result = portfolio(
+            &mut variables,
+            &arcs,
+            &differ(&arcs),
+            configurations(),
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        assert_eq!(result.solution, Solution::Consistent);
+        assert!(result.winner.is_some());
+        let mut values = variables
+            .iter()
+            .map(|v| v.possible_values()[0])
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, vec!['a', 'b', 'c']);
+
+        let (mut variables, arcs, _) = all_different(&[0, 1, 2, 3], &['a', 'b', 'c']);
+        let cancellation = CancellationToken::new();
+        let result = portfolio(
+            &mut variables,
+            &arcs,
+            &differ(&arcs),
+            configurations(),
+            &cancellation,
+        )
+        .unwrap();
+        assert_eq!(result.solution, Solution::NoSolution);
+        // The caller's token is left for reuse.
+        assert!(!cancellation.is_cancelled());
+    }
+
+    #[test]
+    fn portfolio_respects_every_token() {
+        let (variables, arcs, _) = all_different(&[0, 1, 2], &['a', 'b', 'c']);
+
+        // A configuration cancelled on its own leaves the rest running.
+        let mut partly_stopped = configurations();
+        let stopped = CancellationToken::new();
+        stopped.cancel();
+        partly_stopped[0].1.cancellation = Some(stopped);
+        let result = portfolio(
+            &mut variables.clone(),
+            &arcs,
+            &differ(&arcs),
+            partly_stopped,
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        assert_eq!(result.solution, Solution::Consistent);
+        assert_ne!(result.winner, Some(0));
+
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+        let result = portfolio(
+            &mut variables.clone(),
+            &arcs,
+            &differ(&arcs),
+            configurations(),
+            &cancellation,
+        )
+        .unwrap();
+        assert_eq!(result.solution, Solution::LimitReached(Limit::Cancelled));
+
+        let nothing: Vec<(StdRng, SearchOptions)> = vec![];
+        assert!(
+            portfolio(
+                &mut variables.clone(),
+                &arcs,
+                &differ(&arcs),
+                nothing,
+                &CancellationToken::new()
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn portfolio_reports_limits_when_nobody_finishes() {
+        let (mut variables, arcs, _) = all_different(&[0, 1, 2, 3], &['a', 'b', 'c']);
+        let limited = SearchOptions {
+            limits: Limits {
+                nodes: Some(0),
+                ..Limits::default()
+            },
+            ..SearchOptions::default()
+        };
+        let result = portfolio(
+            &mut variables,
+            &arcs,
+            &differ(&arcs),
+            vec![
+                (StdRng::seed_from_u64(1), limited.clone()),
+                (StdRng::seed_from_u64(2), limited),
+            ],
+            &CancellationToken::new(),
+        )
+        .unwrap();
+        assert_eq!(result.solution, Solution::LimitReached(Limit::Nodes));
+        assert_eq!(result.winner, None);
+    }
+}
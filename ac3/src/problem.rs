@@ -0,0 +1,279 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::ac3::{Constraint, DomainType, IdentifierType, propagate};
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// A problem that can change while being edited. Constraints can be added and retracted at any
+/// time, and [`Problem::variables`] always holds the arc consistent domains for the constraints
+/// currently in place.
+///
+/// Adding a constraint only propagates from its arc. Retracting one puts back the values it
+/// removed, along with any value whose removal depended on those, and then propagates from the
+/// variables that got values back, so neither has to start over from the original domains.
+pub struct Problem<D, K> {
+    /// Domains as given, before any propagation.
+    original: VariableProvider<D, K>,
+    variables: VariableProvider<D, K>,
+    arcs: Vec<(VariableID, VariableID)>,
+    constraints: HashMap<(VariableID, VariableID), Constraint<D>>,
+    /// Every value propagation removed, with the neighbour that left it without support.
+    removals: Vec<(VariableID, D, VariableID)>,
+    /// Whether the last propagation stopped at an emptied domain before reaching a fixpoint.
+    wiped_out: bool,
+}
+
+impl<D, K> Problem<D, K>
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    /// Start from `variables`, without any constraints.
+    #[must_use]
+    pub fn new(variables: VariableProvider<D, K>) -> Self {
+        Self {
+            original: variables.clone(),
+            variables,
+            arcs: vec![],
+            constraints: HashMap::new(),
+            removals: vec![],
+            wiped_out: false,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Fails if the identifier is already in use.
+    pub fn add_var(&mut self, id: K, values: Vec<D>) -> Result<VariableID, String> {
+        self.original.add_var(id, values.clone())?;
+        self.variables.add_var(id, values)
+    }
+
+    /// Remove a variable along with every constraint on it, relaxing the domains those
+    /// constraints narrowed.
+    pub fn remove_var(&mut self, id: VariableID) -> Statistics {
+        let mut statistics = Statistics::default();
+        let touching = self
+            .arcs
+            .iter()
+            .filter(|(x, y)| *x == id || *y == id)
+            .copied()
+            .collect::<Vec<_>>();
+        for (x, y) in touching {
+            statistics += self.retract_constraint(x, y);
+        }
+        self.removals.retain(|(v, _, _)| *v != id);
+        self.original.remove_var(id);
+        self.variables.remove_var(id);
+        statistics
+    }
+
+    /// Current domains, arc consistent for the constraints in place.
+    #[must_use]
+    pub fn variables(&self) -> &VariableProvider<D, K> {
+        &self.variables
+    }
+
+    /// Arcs of the constraints in place, in the order they were added.
+    #[must_use]
+    pub fn arcs(&self) -> &[(VariableID, VariableID)] {
+        &self.arcs
+    }
+
+    /// Constraints in place, for searching with e.g. [`crate::backtrack::search`].
+    #[must_use]
+    pub fn constraints(&self) -> &HashMap<(VariableID, VariableID), Constraint<D>> {
+        &self.constraints
+    }
+
+    /// Whether every variable still has a possible value.
+    #[must_use]
+    pub fn is_consistent(&self) -> bool {
+        self.variables.is_consistent()
+    }
+
+    /// Constrain the arc from `x` to `y` and propagate. A constraint already on that arc is
+    /// retracted first, since the new one may be looser. Does nothing if either variable was
+    /// removed.
+    pub fn add_constraint(
+        &mut self,
+        x: VariableID,
+        y: VariableID,
+        constraint: Constraint<D>,
+    ) -> Statistics {
+        if self.variables.get_var(x).is_none() || self.variables.get_var(y).is_none() {
+            return Statistics::default();
+        }
+        let mut statistics = self.retract_constraint(x, y);
+        self.constraints.insert((x, y), constraint);
+        self.arcs.push((x, y));
+        statistics += self.propagate(VecDeque::from([(x, y)]));
+        statistics
+    }
+
+    /// Remove the constraint on the arc from `x` to `y`, relaxing the domains it narrowed.
+    pub fn retract_constraint(&mut self, x: VariableID, y: VariableID) -> Statistics {
+        if self.constraints.remove(&(x, y)).is_none() {
+            return Statistics::default();
+        }
+        self.arcs.retain(|arc| *arc != (x, y));
+
+        // Values the arc removed come back, then any value removed for lack of support in a
+        // variable that got values back, and so on.
+        let mut restored = vec![];
+        let mut touched = vec![];
+        self.removals.retain(|(v, value, neighbour)| {
+            let back = (*v, *neighbour) == (x, y);
+            if back {
+                restored.push((*v, *value));
+            }
+            !back
+        });
+        if !restored.is_empty() {
+            touched.push(x);
+        }
+        let mut index = 0;
+        while let Some(&w) = touched.get(index) {
+            index += 1;
+            self.removals.retain(|(v, value, neighbour)| {
+                let back = *neighbour == w;
+                if back {
+                    restored.push((*v, *value));
+                    if !touched.contains(v) {
+                        touched.push(*v);
+                    }
+                }
+                !back
+            });
+        }
+
+        for v in &touched {
+            let (Some(original), Some(current)) =
+                (self.original.get_var(*v), self.variables.get_var(*v))
+            else {
+                continue;
+            };
+            // Keep the original order of values.
+            let values = original
+                .possible_values()
+                .iter()
+                .filter(|value| {
+                    current.possible_values().contains(value) || restored.contains(&(*v, **value))
+                })
+                .copied()
+                .collect::<Vec<_>>();
+            current.replace_possible_values(values);
+        }
+
+        // Only variables that got values back can lose some again.
+        let queue = self
+            .arcs
+            .iter()
+            .filter(|(a, _)| touched.contains(a))
+            .copied()
+            .collect();
+        self.propagate(queue)
+    }
+
+    fn propagate(&mut self, queue: VecDeque<(VariableID, VariableID)>) -> Statistics {
+        // An earlier wipeout left arcs unrevised, so start over from all of them.
+        let queue = if self.wiped_out {
+            self.arcs.iter().copied().collect()
+        } else {
+            queue
+        };
+        let mut statistics = Statistics::default();
+        let mut removals = vec![];
+        let wipeout = propagate(
+            &self.variables,
+            &self.arcs,
+            &self.constraints,
+            queue,
+            &mut statistics,
+            |x, y, removed| removals.extend(removed.iter().map(|value| (x, *value, y))),
+            |_| true,
+        );
+        self.removals.extend(removals);
+        self.wiped_out = wipeout.is_some();
+        statistics
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ac3::new_constraint, test_util::domain, variable_provider::VariableProvider};
+
+    use super::Problem;
+
+    #[test]
+    fn retracting_relaxes_domains() {
+        let mut problem = Problem::new(VariableProvider::from([
+            ('a', vec![1, 2, 3, 4]),
+            ('b', vec![1, 2, 3, 4]),
+            ('c', vec![1, 2, 3, 4]),
+        ]));
+        let [a, b, c] = ['a', 'b', 'c'].map(|id| problem.variables().find_id(id).unwrap());
+
+        // a < b < c.
+        problem.add_constraint(a, b, new_constraint(|x: &i32, y: &i32| x < y));
+        problem.add_constraint(b, a, new_constraint(|x: &i32, y: &i32| x > y));
+        problem.add_constraint(b, c, new_constraint(|x: &i32, y: &i32| x < y));
+        problem.add_constraint(c, b, new_constraint(|x: &i32, y: &i32| x > y));
+        assert_eq!(domain(problem.variables(), 'a'), vec![1, 2]);
+        assert_eq!(domain(problem.variables(), 'b'), vec![2, 3]);
+        assert_eq!(domain(problem.variables(), 'c'), vec![3, 4]);
+
+        // Dropping b < c gives back b = 4, and through it a = 3, as well as c = 1, 2.
+        problem.retract_constraint(b, c);
+        problem.retract_constraint(c, b);
+        assert_eq!(domain(problem.variables(), 'a'), vec![1, 2, 3]);
+        assert_eq!(domain(problem.variables(), 'b'), vec![2, 3, 4]);
+        assert_eq!(domain(problem.variables(), 'c'), vec![1, 2, 3, 4]);
+        assert_eq!(problem.arcs(), &[(a, b), (b, a)]);
+    }
+
+    #[test]
+    fn retracting_recovers_from_wipeouts() {
+        let mut problem = Problem::new(VariableProvider::from([
+            ('a', vec![1, 2]),
+            ('b', vec![1, 2]),
+        ]));
+        let [a, b] = ['a', 'b'].map(|id| problem.variables().find_id(id).unwrap());
+
+        problem.add_constraint(a, b, new_constraint(|x: &i32, y: &i32| x < y));
+        problem.add_constraint(b, a, new_constraint(|x: &i32, y: &i32| x > y));
+        problem.add_constraint(a, b, new_constraint(|x: &i32, y: &i32| x > y));
+        assert!(!problem.is_consistent());
+
+        // Replacing the constraint again keeps b > a from before.
+        problem.add_constraint(a, b, new_constraint(|x: &i32, y: &i32| x != y));
+        assert!(problem.is_consistent());
+        assert_eq!(domain(problem.variables(), 'a'), vec![1]);
+        assert_eq!(domain(problem.variables(), 'b'), vec![2]);
+
+        problem.retract_constraint(b, a);
+        assert_eq!(domain(problem.variables(), 'a'), vec![1, 2]);
+        assert_eq!(domain(problem.variables(), 'b'), vec![1, 2]);
+    }
+
+    #[test]
+    fn removing_a_variable_drops_its_constraints() {
+        let mut problem = Problem::new(VariableProvider::from([('a', vec![1, 2])]));
+        let a = problem.variables().find_id('a').unwrap();
+        let b = problem.add_var('b', vec![1]).unwrap();
+        problem.add_constraint(a, b, new_constraint(|x: &i32, y: &i32| x != y));
+        assert_eq!(domain(problem.variables(), 'a'), vec![2]);
+
+        problem.remove_var(b);
+        assert!(problem.variables().get_var(b).is_none());
+        assert!(problem.arcs().is_empty());
+        assert_eq!(domain(problem.variables(), 'a'), vec![1, 2]);
+
+        // A new variable does not pick up the old one's constraints.
+        let c = problem.add_var('c', vec![2]).unwrap();
+        assert_ne!(b, c);
+        problem.add_constraint(b, a, new_constraint(|_: &i32, _: &i32| false));
+        assert!(problem.arcs().is_empty());
+        assert!(problem.is_consistent());
+    }
+}
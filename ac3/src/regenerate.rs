@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+
+use rand::Rng;
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use crate::backtrack::{SearchOptions, Solution, search};
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Re-roll part of a solved map. The variables in `free` get their domains from `original` back
+/// and are searched again, while every other variable keeps its value from `solved`.
+///
+/// Only arcs touching a freed variable are propagated, which keeps propagation to the region.
+/// The search itself still copies every variable at each decision and scans them all to pick the
+/// next one, so the rest of the map adds to the cost. On anything but [`Solution::Consistent`],
+/// `solved` is left as it was.
+#[must_use]
+pub fn regenerate<K, D, CP, R>(
+    solved: &mut VariableProvider<D, K>,
+    original: &VariableProvider<D, K>,
+    free: &[VariableID],
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    options: &SearchOptions,
+) -> (Solution, Statistics)
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    let mut region = solved.clone();
+    for v in free {
+        if let Some(var) = original.get_var(*v) {
+            region.update_var(*v, var.possible_values().clone());
+        }
+    }
+    let free = free.iter().collect::<HashSet<_>>();
+    let arcs = arcs
+        .iter()
+        .filter(|(x, y)| free.contains(x) || free.contains(y))
+        .copied()
+        .collect::<Vec<_>>();
+
+    let (solution, statistics) = search(&mut region, &arcs, constraints, rng, options);
+    if solution == Solution::Consistent {
+        *solved = region;
+    }
+    (solution, statistics)
+}
+
+/// Same as [`regenerate`], freeing every variable whose identifier matches `predicate`, e.g.
+/// the cells inside a rectangle.
+#[must_use]
+pub fn regenerate_matching<K, D, CP, R, P>(
+    solved: &mut VariableProvider<D, K>,
+    original: &VariableProvider<D, K>,
+    predicate: P,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    options: &SearchOptions,
+) -> (Solution, Statistics)
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+    P: Fn(&K) -> bool,
+{
+    let free = solved
+        .iter()
+        .filter(|v| predicate(&v.identifier))
+        .map(|v| v.index)
+        .collect::<Vec<_>>();
+    regenerate(solved, original, &free, arcs, constraints, rng, options)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        ac3::new_constraint,
+        backtrack::{SearchOptions, Solution, search},
+        test_util::simple_rng,
+        variable_provider::VariableProvider,
+    };
+
+    use super::{regenerate, regenerate_matching};
+
+    fn values(variables: &VariableProvider<i32, char>) -> Vec<i32> {
+        ['a', 'b', 'c', 'd']
+            .map(|id| variables.find_var(id).unwrap().possible_values()[0])
+            .to_vec()
+    }
+
+    #[test]
+    fn regenerate_keeps_everything_outside_the_region() {
+        let mut rng = simple_rng("regenerate");
+        // Neighbours along a line differ.
+        let original = VariableProvider::from([
+            ('a', vec![1, 2, 3]),
+            ('b', vec![1, 2, 3]),
+            ('c', vec![1, 2, 3]),
+            ('d', vec![1, 2, 3]),
+        ]);
+        let [a, b, c, d] = ['a', 'b', 'c', 'd'].map(|id| original.find_id(id).unwrap());
+        let mut arcs = vec![];
+        let mut constraints = HashMap::new();
+        for (x, y) in [(a, b), (b, c), (c, d)] {
+            for arc in [(x, y), (y, x)] {
+                arcs.push(arc);
+                constraints.insert(arc, new_constraint(|p: &i32, q: &i32| p != q));
+            }
+        }
+        let options = SearchOptions::default();
+
+        let mut solved = original.clone();
+        let (solution, _) = search(&mut solved, &arcs, &constraints, &mut rng, &options);
+        assert_eq!(solution, Solution::Consistent);
+        let before = values(&solved);
+
+        for _ in 0..10 {
+            let (solution, _) = regenerate_matching(
+                &mut solved,
+                &original,
+                |id| *id == 'b' || *id == 'c',
+                &arcs,
+                &constraints,
+                &mut rng,
+                &options,
+            );
+            assert_eq!(solution, Solution::Consistent);
+            let after = values(&solved);
+            assert_eq!((after[0], after[3]), (before[0], before[3]));
+            assert!(after.windows(2).all(|pair| pair[0] != pair[1]));
+        }
+
+        // b can only take a's value, so nothing changes.
+        let mut narrow = original.clone();
+        narrow.update_var(b, vec![before[0]]);
+        let kept = values(&solved);
+        let (solution, _) = regenerate(
+            &mut solved,
+            &narrow,
+            &[b],
+            &arcs,
+            &constraints,
+            &mut rng,
+            &options,
+        );
+        assert_eq!(solution, Solution::NoSolution);
+        assert_eq!(values(&solved), kept);
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::Rng;
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use crate::backtrack::{SearchOptions, Solution, Solver};
+use crate::limits::Limit;
+use crate::observer::SearchObserver;
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// How [`search_with_repair`] recovers from contradictions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RepairPolicy {
+    /// How many arcs away from the contradiction to reset variables. Each further repair
+    /// around the same variable reaches one arc further.
+    pub radius: usize,
+    /// Give up with [`Solution::NoSolution`] after this many repairs.
+    pub max_repairs: u64,
+}
+
+impl Default for RepairPolicy {
+    fn default() -> Self {
+        Self {
+            radius: 1,
+            max_repairs: 10,
+        }
+    }
+}
+
+/// Result of [`search_with_repair`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Repaired {
+    pub solution: Solution,
+    /// Variables reset to their original domains, in the order they were reset.
+    pub reset: Vec<VariableID>,
+    /// Number of repairs made.
+    pub repairs: u64,
+    /// Summed over every attempt, i.e. the first search and the one after each repair.
+    pub statistics: Statistics,
+}
+
+/// Counts how often each variable was emptied, in the order they first were.
+#[derive(Default)]
+struct Wipeouts {
+    counts: HashMap<VariableID, u64>,
+    order: Vec<VariableID>,
+}
+
+impl Wipeouts {
+    /// The variable emptied most often, the first of them on a tie.
+    fn most_frequent(&self) -> Option<VariableID> {
+        let mut most = None;
+        for v in &self.order {
+            let count = self.counts[v];
+            if most.is_none_or(|(_, most)| count > most) {
+                most = Some((*v, count));
+            }
+        }
+        most.map(|(v, _)| v)
+    }
+}
+
+impl<D, K> SearchObserver<D, K> for Wipeouts {
+    fn on_wipeout(&mut self, variable: VariableID) {
+        let count = self.counts.entry(variable).or_default();
+        if *count == 0 {
+            self.order.push(variable);
+        }
+        *count += 1;
+    }
+}
+
+/// Same as [`crate::backtrack::search`], but instead of failing when `variables` has no solution,
+/// reset the variables around the contradiction to their domains in `original` and search again.
+/// `original` is typically the map before fixed regions were inserted, so a repair gives up some
+/// of those placements rather than the whole map.
+///
+/// Besides proving there is no solution, reaching the node or backtrack limit of `options` counts
+/// as running into a contradiction, since on a large map the proof may never finish. The limits
+/// apply to each attempt, so they should leave enough room to solve a map that has no
+/// contradiction. The contradiction is located at the variable emptied most often during the
+/// attempt, where the search kept failing. Once [`RepairPolicy::max_repairs`] is used up, the
+/// outcome of the last attempt is returned.
+#[must_use]
+pub fn search_with_repair<K, D, CP, R>(
+    variables: &mut VariableProvider<D, K>,
+    original: &VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    options: &SearchOptions,
+    policy: &RepairPolicy,
+) -> Repaired
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    let mut statistics = Statistics::default();
+    let mut reset = vec![];
+    let mut repairs = 0;
+    let mut attempts: HashMap<VariableID, usize> = HashMap::new();
+
+    loop {
+        let start = variables.clone();
+        let mut wipeouts = Wipeouts::default();
+        let mut solver = Solver::new(std::mem::take(variables), arcs, constraints, rng, options)
+            .with_observer(&mut wipeouts);
+        let solution = solver.run();
+        statistics += solver.statistics();
+        *variables = solver.into_variables();
+
+        let contradiction = matches!(
+            solution,
+            Solution::NoSolution | Solution::LimitReached(Limit::Nodes | Limit::Backtracks)
+        );
+        if !contradiction || repairs >= policy.max_repairs {
+            return Repaired {
+                solution,
+                reset,
+                repairs,
+                statistics,
+            };
+        }
+
+        // Without a wipeout, some domain was empty from the start.
+        let Some(centre) = wipeouts.most_frequent().or_else(|| {
+            start
+                .iter()
+                .find(|v| v.possible_values().is_empty())
+                .map(|v| v.index)
+        }) else {
+            return Repaired {
+                solution,
+                reset,
+                repairs,
+                statistics,
+            };
+        };
+        let attempt = attempts.entry(centre).or_default();
+        let radius = policy.radius + *attempt;
+        *attempt += 1;
+
+        *variables = start;
+        for v in neighbourhood(arcs, centre, radius) {
+            if let Some(domain) = original.get_var(v) {
+                variables.update_var(v, domain.possible_values().clone());
+                if !reset.contains(&v) {
+                    reset.push(v);
+                }
+            }
+        }
+        repairs += 1;
+    }
+}
+
+/// Variables at most `radius` arcs away from `centre`, nearest first.
+fn neighbourhood(
+    arcs: &[(VariableID, VariableID)],
+    centre: VariableID,
+    radius: usize,
+) -> Vec<VariableID> {
+    let mut found = vec![centre];
+    let mut seen = HashSet::from([centre]);
+    let mut frontier = HashSet::from([centre]);
+    for _ in 0..radius {
+        let mut next = vec![];
+        for &(x, y) in arcs {
+            for (from, to) in [(x, y), (y, x)] {
+                if frontier.contains(&from) && seen.insert(to) {
+                    next.push(to);
+                }
+            }
+        }
+        found.extend(&next);
+        frontier = next.into_iter().collect();
+    }
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        backtrack::{SearchOptions, Solution},
+        limits::{Limit, Limits},
+        observer::SearchObserver,
+        test_util::{all_different, simple_rng},
+        variable_provider::VariableProvider,
+    };
+
+    use super::{RepairPolicy, Wipeouts, neighbourhood, search_with_repair};
+
+    #[test]
+    fn neighbourhood_grows_with_radius() {
+        let variables = VariableProvider::from([
+            ('a', vec![1]),
+            ('b', vec![1]),
+            ('c', vec![1]),
+            ('d', vec![1]),
+        ]);
+        let [a, b, c, d] = ['a', 'b', 'c', 'd'].map(|id| variables.find_id(id).unwrap());
+        let arcs = [(a, b), (b, c), (c, d)];
+        assert_eq!(neighbourhood(&arcs, b, 0), vec![b]);
+        assert_eq!(neighbourhood(&arcs, b, 1), vec![b, a, c]);
+        assert_eq!(neighbourhood(&arcs, b, 2), vec![b, a, c, d]);
+    }
+
+    #[test]
+    fn most_frequent_wipeout_wins() {
+        let variables = VariableProvider::from([('a', vec![1]), ('b', vec![1])]);
+        let [a, b] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+        let mut wipeouts = Wipeouts::default();
+        assert_eq!(wipeouts.most_frequent(), None);
+        for v in [a, b, b, a] {
+            SearchObserver::<u32, char>::on_wipeout(&mut wipeouts, v);
+        }
+        assert_eq!(wipeouts.most_frequent(), Some(a));
+        SearchObserver::<u32, char>::on_wipeout(&mut wipeouts, b);
+        assert_eq!(wipeouts.most_frequent(), Some(b));
+    }
+
+    #[test]
+    fn repair_when_the_search_gives_up() {
+        // Five pigeons pinned into four holes, which only a search finds out.
+        let ids = ['a', 'b', 'c', 'd', 'e', 'f'];
+        let (original, arcs, constraints) = all_different(&ids, &[1, 2, 3, 4, 5, 6]);
+        let pinned = || {
+            let mut variables = original.clone();
+            for id in &ids[..5] {
+                variables.update_var(original.find_id(*id).unwrap(), vec![1, 2, 3, 4]);
+            }
+            variables
+        };
+        let mut variables = pinned();
+        let options = SearchOptions {
+            limits: Limits {
+                backtracks: Some(5),
+                ..Limits::default()
+            },
+            ..SearchOptions::default()
+        };
+
+        let repaired = search_with_repair(
+            &mut variables,
+            &original,
+            &arcs,
+            &constraints,
+            &mut simple_rng("give up"),
+            &options,
+            &RepairPolicy {
+                radius: 0,
+                max_repairs: 3,
+            },
+        );
+        assert_eq!(repaired.solution, Solution::Consistent);
+        assert!(repaired.repairs >= 1);
+        assert!(variables.iter().all(|v| v.possible_values().len() == 1));
+
+        let repaired = search_with_repair(
+            &mut pinned(),
+            &original,
+            &arcs,
+            &constraints,
+            &mut simple_rng("give up"),
+            &options,
+            &RepairPolicy {
+                radius: 0,
+                max_repairs: 0,
+            },
+        );
+        assert_eq!(repaired.solution, Solution::LimitReached(Limit::Backtracks));
+    }
+
+    #[test]
+    fn repair_resets_conflicting_pins() {
+        let (original, arcs, constraints) = all_different(&['a', 'b', 'c'], &[1, 2, 3]);
+        let [a, b] = ['a', 'b'].map(|id| original.find_id(id).unwrap());
+        let pinned = || {
+            let mut variables = original.clone();
+            variables.update_var(a, vec![1]);
+            variables.update_var(b, vec![1]);
+            variables
+        };
+        let mut rng = simple_rng("repair");
+
+        let mut variables = pinned();
+        let repaired = search_with_repair(
+            &mut variables,
+            &original,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &SearchOptions::default(),
+            &RepairPolicy {
+                radius: 0,
+                max_repairs: 3,
+            },
+        );
+        assert_eq!(repaired.solution, Solution::Consistent);
+        assert_eq!(repaired.repairs, 1);
+        assert_eq!(repaired.reset.len(), 1);
+        assert!(variables.iter().all(|v| v.possible_values().len() == 1));
+        // Whichever pin was not reset survives.
+        let kept = if repaired.reset[0] == a { b } else { a };
+        assert_eq!(variables.get_var(kept).unwrap().possible_values()[0], 1);
+
+        let mut variables = pinned();
+        let repaired = search_with_repair(
+            &mut variables,
+            &original,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &SearchOptions::default(),
+            &RepairPolicy {
+                radius: 0,
+                max_repairs: 0,
+            },
+        );
+        assert_eq!(repaired.solution, Solution::NoSolution);
+        assert!(repaired.reset.is_empty());
+    }
+}
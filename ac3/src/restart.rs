@@ -0,0 +1,84 @@
+/// How many backtracks a search may make before abandoning the current tree and starting over
+/// from the root. Restarting keeps the random number generator going, so each attempt explores
+/// differently, and keeps learned variable weights (see
+/// [`crate::backtrack::VariableOrdering::DomWdeg`]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RestartPolicy {
+    /// Restart every time the search has backtracked this many times.
+    Fixed(u64),
+    /// Scale the [Luby sequence](https://doi.org/10.1016/0020-0190(93)90029-9) (1, 1, 2, 1, 1, 2,
+    /// 4, ...) by this many backtracks.
+    Luby(u64),
+    /// Start at `first` backtracks and multiply by `factor` after every restart.
+    Geometric { first: u64, factor: f64 },
+}
+
+impl RestartPolicy {
+    /// The number of backtracks allowed before restart number `restarts` (counting from 0). Always
+    /// at least 1, so a zero budget or a `factor` below 1 (or NaN) can't restart forever.
+    #[must_use]
+    // The limit is rounded and saturated into a u64 on purpose.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss,
+        clippy::cast_possible_wrap
+    )]
+    pub fn limit(&self, restarts: u64) -> u64 {
+        let limit = match *self {
+            RestartPolicy::Fixed(backtracks) => backtracks,
+            RestartPolicy::Luby(unit) => unit.saturating_mul(luby(restarts + 1)),
+            RestartPolicy::Geometric { first, factor } => {
+                let exponent = restarts.min(i32::MAX as u64) as i32;
+                (first as f64 * factor.powi(exponent)).ceil() as u64
+            }
+        };
+        limit.max(1)
+    }
+}
+
+/// The `i`th element of the Luby sequence, counting from 1.
+fn luby(mut i: u64) -> u64 {
+    loop {
+        // Each run of the sequence ends at index 2^k - 1 with the value 2^(k-1).
+        let mut k = 1;
+        while (1 << k) - 1 < i {
+            k += 1;
+        }
+        if (1 << k) - 1 == i {
+            return 1 << (k - 1);
+        }
+        // Otherwise i lands in a repeat of an earlier run.
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RestartPolicy, luby};
+
+    #[test]
+    fn luby_sequence() {
+        let sequence = (1..=15).map(luby).collect::<Vec<_>>();
+        assert_eq!(sequence, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn restart_limits() {
+        assert_eq!(RestartPolicy::Fixed(7).limit(3), 7);
+        assert_eq!(RestartPolicy::Luby(10).limit(2), 20);
+        let geometric = RestartPolicy::Geometric {
+            first: 10,
+            factor: 1.5,
+        };
+        assert_eq!(geometric.limit(0), 10);
+        assert_eq!(geometric.limit(1), 15);
+        assert_eq!(geometric.limit(2), 23);
+
+        assert_eq!(RestartPolicy::Fixed(0).limit(0), 1);
+        for factor in [0.5, f64::NAN] {
+            let shrinking = RestartPolicy::Geometric { first: 10, factor };
+            assert_eq!(shrinking.limit(20), 1);
+        }
+    }
+}
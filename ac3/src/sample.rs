@@ -0,0 +1,404 @@
+use std::cmp::Reverse;
+
+use rand::{Rng, RngExt};
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType, ac3};
+use crate::backtrack::{SearchOptions, Solution, Solver, ValueOrdering};
+use crate::limits::Limit;
+use crate::optimize::{Objective, optimize};
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Tuning for [`sample`].
+#[derive(Clone, Debug)]
+pub struct SampleOptions {
+    /// Stop counting the solutions below a value after this many. Higher limits sample more
+    /// uniformly, at the cost of enumerating more solutions.
+    pub count_limit: u64,
+    /// Limits and cancellation for the searches that count solutions. The rest is ignored, since
+    /// those searches must visit each solution exactly once, so they always run a plain depth
+    /// first search.
+    pub search: SearchOptions,
+}
+
+impl Default for SampleOptions {
+    fn default() -> Self {
+        Self {
+            count_limit: 1000,
+            search: SearchOptions::default(),
+        }
+    }
+}
+
+/// Result of [`sample`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Sampled {
+    pub solution: Solution,
+    /// Decisions where some value allowed at least [`SampleOptions::count_limit`] solutions, so
+    /// the values were weighted by capped counts rather than exactly. The distribution is only
+    /// uniform when this is 0.
+    pub capped: u64,
+    pub statistics: Statistics,
+}
+
+/// Pick a solution approximately uniformly at random, unlike [`crate::backtrack::reduce`] which
+/// favours solutions reachable through few choices. Variables are decided one at a time, choosing
+/// each value with probability proportional to the number of solutions it allows.
+///
+/// Counts are exact, and so is the distribution, as long as they stay below
+/// [`SampleOptions::count_limit`]. Once they reach it, every capped value weighs the same, which
+/// brings back the bias of [`crate::backtrack::reduce`]; [`Sampled::capped`] reports how often
+/// that happened. Counting enumerates up to that many solutions for every value of every
+/// variable, so this is only practical for small problems, e.g. a few dozen variables, and not
+/// for whole maps. On [`Solution::Consistent`] each variable in `variables` is left with exactly
+/// one possible value.
+#[must_use]
+pub fn sample<K, D, CP, R>(
+    variables: &mut VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    options: &SampleOptions,
+) -> Sampled
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    let mut statistics = ac3(variables, arcs, constraints);
+    let mut capped = 0;
+    let sampled = |solution, capped, statistics| Sampled {
+        solution,
+        capped,
+        statistics,
+    };
+    if !variables.is_consistent() {
+        return sampled(Solution::NoSolution, capped, statistics);
+    }
+
+    let ids = variables.iter().map(|v| v.index).collect::<Vec<_>>();
+    for v in ids {
+        let Some(values) = variables
+            .get_var(v)
+            .map(|var| var.possible_values().clone())
+        else {
+            continue;
+        };
+        if values.len() < 2 {
+            continue;
+        }
+
+        let mut counts = Vec::with_capacity(values.len());
+        for value in &values {
+            let mut trial = variables.clone();
+            trial.update_var(v, vec![*value]);
+            let (count, count_statistics) = count(trial, arcs, constraints, rng, options);
+            statistics += count_statistics;
+            match count {
+                Ok(count) => counts.push(count),
+                Err(limit) => {
+                    return sampled(Solution::LimitReached(limit), capped, statistics);
+                }
+            }
+        }
+        if counts.iter().any(|count| *count >= options.count_limit) {
+            capped += 1;
+        }
+
+        let total = counts.iter().sum::<u64>();
+        if total == 0 {
+            return sampled(Solution::NoSolution, capped, statistics);
+        }
+        let mut pick = rng.random_range(0..total);
+        let chosen = values
+            .iter()
+            .zip(&counts)
+            .find(|(_, count)| {
+                if pick < **count {
+                    true
+                } else {
+                    pick -= **count;
+                    false
+                }
+            })
+            .map(|(value, _)| *value);
+
+        if let Some(value) = chosen {
+            variables.update_var(v, vec![value]);
+            statistics.decisions += 1;
+            statistics += ac3(variables, arcs, constraints);
+        }
+    }
+
+    sampled(Solution::Consistent, capped, statistics)
+}
+
+/// Number of solutions of `variables`, up to [`SampleOptions::count_limit`].
+fn count<K, D, CP, R>(
+    variables: VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    options: &SampleOptions,
+) -> (Result<u64, Limit>, Statistics)
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    // Restarts and discrepancy search can visit a solution more than once.
+    let counting = SearchOptions {
+        limits: options.search.limits.clone(),
+        cancellation: options.search.cancellation.clone(),
+        value_ordering: ValueOrdering::First,
+        ..SearchOptions::default()
+    };
+    let mut solver = Solver::new(variables, arcs, constraints, rng, &counting);
+    let mut count = 0;
+    while count < options.count_limit {
+        match solver.run() {
+            Solution::Consistent => count += 1,
+            Solution::NoSolution => break,
+            Solution::LimitReached(limit) => return (Err(limit), solver.statistics()),
+        }
+    }
+    (Ok(count), solver.statistics())
+}
+
+/// Scores an assignment by its smallest Hamming distance to a set of earlier solutions, negated
+/// so [`optimize`] maximizes it.
+struct Diversity<D> {
+    chosen: Vec<Vec<(VariableID, D)>>,
+}
+
+impl<D, K> Objective<D, K> for Diversity<D>
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    type Cost = Reverse<usize>;
+
+    fn cost(&self, variables: &VariableProvider<D, K>) -> Self::Cost {
+        self.distance(variables, |values, value| values.first() != Some(value))
+    }
+
+    fn bound(&self, variables: &VariableProvider<D, K>) -> Option<Self::Cost> {
+        // A variable can still differ if any of its values does.
+        Some(self.distance(variables, |values, value| values.iter().any(|v| v != value)))
+    }
+}
+
+impl<D> Diversity<D>
+where
+    D: DomainType,
+{
+    fn distance<K, F>(&self, variables: &VariableProvider<D, K>, differs: F) -> Reverse<usize>
+    where
+        K: IdentifierType,
+        F: Fn(&[D], &D) -> bool,
+    {
+        let distance = self
+            .chosen
+            .iter()
+            .map(|solution| {
+                solution
+                    .iter()
+                    .filter(|(v, value)| {
+                        variables
+                            .get_var(*v)
+                            .is_some_and(|var| differs(&var.possible_values(), value))
+                    })
+                    .count()
+            })
+            .min()
+            .unwrap_or(0);
+        Reverse(distance)
+    }
+}
+
+/// Up to `k` solutions chosen to differ from each other as much as possible, for varied level
+/// generation. Each solution after the first maximizes its smallest Hamming distance (the number
+/// of variables with a different value) to the ones before it.
+///
+/// Each solution after the first takes a whole branch and bound [`optimize`] run, which only
+/// finishes once it has ruled out every more distant solution, so this can take far longer than
+/// `k` plain searches. [`SearchOptions::limits`] in `options` apply to each run separately, and a
+/// run stopped by them settles for the most distant solution it found so far.
+///
+/// Fewer than `k` solutions are returned if there are no more, or if `options` stops a search
+/// before it finds one.
+#[must_use]
+pub fn diverse<K, D, CP, R>(
+    variables: &VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    k: usize,
+    options: &SearchOptions,
+) -> (Vec<VariableProvider<D, K>>, Statistics)
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    let mut statistics = Statistics::default();
+    let mut solutions: Vec<VariableProvider<D, K>> = vec![];
+    let mut objective = Diversity { chosen: vec![] };
+
+    while solutions.len() < k {
+        let mut candidate = variables.clone();
+        let optimum = optimize(&mut candidate, arcs, constraints, rng, &objective, options);
+        statistics += optimum.statistics;
+        // A distance of 0 repeats an earlier solution, but the first one is always welcome.
+        if optimum.solution != Solution::Consistent
+            || (!solutions.is_empty() && optimum.cost == Some(Reverse(0)))
+        {
+            break;
+        }
+
+        objective.chosen.push(
+            candidate
+                .iter()
+                .filter_map(|var| {
+                    var.possible_values()
+                        .first()
+                        .map(|value| (var.index, *value))
+                })
+                .collect(),
+        );
+        solutions.push(candidate);
+    }
+
+    (solutions, statistics)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        ac3::{Constraint, new_constraint},
+        backtrack::{SearchOptions, Solution, Strategy, ValueOrdering},
+        restart::RestartPolicy,
+        test_util::{all_different, simple_rng, value},
+        variable_provider::{VariableID, VariableProvider},
+    };
+
+    use super::{SampleOptions, count, diverse, sample};
+
+    #[test]
+    fn sample_is_close_to_uniform() {
+        let mut rng = simple_rng("sample");
+        // a = 1 forces b = 1, otherwise b is free: 5 solutions, one of them with a = 1. Deciding
+        // a first at random would pick that one half of the time.
+        let original = VariableProvider::from([('a', vec![1, 2]), ('b', vec![1, 2, 3, 4])]);
+        let a = original.find_id('a').unwrap();
+        let b = original.find_id('b').unwrap();
+        let constraints: HashMap<(VariableID, VariableID), Constraint<i32>> = HashMap::from([
+            (
+                (a, b),
+                new_constraint(|x: &i32, y: &i32| *x != 1 || *y == 1),
+            ),
+            (
+                (b, a),
+                new_constraint(|y: &i32, x: &i32| *x != 1 || *y == 1),
+            ),
+        ]);
+        let arcs = [(a, b), (b, a)];
+
+        let mut forced = 0;
+        for _ in 0..500 {
+            let mut variables = original.clone();
+            let sampled = sample(
+                &mut variables,
+                &arcs,
+                &constraints,
+                &mut rng,
+                &SampleOptions::default(),
+            );
+            assert_eq!(sampled.solution, Solution::Consistent);
+            assert_eq!(sampled.capped, 0);
+            if value(&variables, 'a') == 1 {
+                assert_eq!(value(&variables, 'b'), 1);
+                forced += 1;
+            }
+        }
+        assert!((60..140).contains(&forced), "{forced}");
+    }
+
+    #[test]
+    fn capped_counts_are_reported() {
+        let (original, arcs, constraints) = all_different(&['a', 'b', 'c'], &[1, 2, 3, 4]);
+        // Every value of a allows 6 solutions, b's then allow 2 and c's 1. A count that
+        // reaches the limit may have stopped there, so it counts as capped.
+        for (count_limit, capped) in [(7, 0), (6, 1), (2, 2), (1, 3)] {
+            let mut variables = original.clone();
+            let sampled = sample(
+                &mut variables,
+                &arcs,
+                &constraints,
+                &mut simple_rng("capped"),
+                &SampleOptions {
+                    count_limit,
+                    ..SampleOptions::default()
+                },
+            );
+            assert_eq!(sampled.solution, Solution::Consistent);
+            assert_eq!(sampled.capped, capped, "count_limit {count_limit}");
+        }
+    }
+
+    #[test]
+    fn counts_ignore_strategies_that_revisit_solutions() {
+        let mut rng = simple_rng("count");
+        let (variables, arcs, constraints) = all_different(&['a', 'b', 'c'], &[1, 2, 3, 4]);
+        let revisiting = SampleOptions {
+            search: SearchOptions {
+                strategy: Strategy::LimitedDiscrepancy,
+                value_ordering: ValueOrdering::Random,
+                restarts: Some(RestartPolicy::Fixed(1)),
+                ..SearchOptions::default()
+            },
+            ..SampleOptions::default()
+        };
+        let (count, _) = count(variables, &arcs, &constraints, &mut rng, &revisiting);
+        assert_eq!(count, Ok(24));
+    }
+
+    #[test]
+    fn diverse_solutions_differ_everywhere() {
+        let mut rng = simple_rng("diverse");
+        let (variables, arcs, constraints) = all_different(&['a', 'b', 'c'], &[1, 2, 3]);
+
+        let (solutions, _) = diverse(
+            &variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            3,
+            &SearchOptions::default(),
+        );
+        assert_eq!(solutions.len(), 3);
+        for (i, x) in solutions.iter().enumerate() {
+            for y in &solutions[i + 1..] {
+                for id in ['a', 'b', 'c'] {
+                    assert_ne!(value(x, id), value(y, id));
+                }
+            }
+        }
+
+        // Only 6 permutations exist.
+        let (solutions, _) = diverse(
+            &variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            10,
+            &SearchOptions::default(),
+        );
+        assert_eq!(solutions.len(), 6);
+    }
+}
@@ -0,0 +1,64 @@
+use std::ops::AddAssign;
+
+/// Counters describing how much work propagation and search needed, useful for comparing
+/// tilesets and heuristics.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// Calls to [`crate::ac3::ConstraintProvider::check`].
+    pub checks: u64,
+    /// Arcs taken from the queue and revised.
+    pub revisions: u64,
+    /// Revisions that removed at least one value.
+    pub reductions: u64,
+    /// Values chosen for a variable by the search.
+    pub decisions: u64,
+    /// Times the search undid a decision.
+    pub backtracks: u64,
+    /// Deepest stack of decisions the search held at once.
+    pub max_depth: usize,
+    /// Times the search started over from the root.
+    pub restarts: u64,
+    /// Nogoods learned by the search.
+    pub nogoods: u64,
+}
+
+impl AddAssign for Statistics {
+    fn add_assign(&mut self, rhs: Self) {
+        self.checks += rhs.checks;
+        self.revisions += rhs.revisions;
+        self.reductions += rhs.reductions;
+        self.decisions += rhs.decisions;
+        self.backtracks += rhs.backtracks;
+        self.max_depth = self.max_depth.max(rhs.max_depth);
+        self.restarts += rhs.restarts;
+        self.nogoods += rhs.nogoods;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Statistics;
+
+    #[test]
+    fn add_assign_sums_counters_and_keeps_deepest() {
+        let mut stats = Statistics {
+            checks: 1,
+            revisions: 2,
+            reductions: 3,
+            decisions: 4,
+            backtracks: 5,
+            max_depth: 6,
+            restarts: 7,
+            nogoods: 8,
+        };
+        stats += Statistics {
+            checks: 10,
+            max_depth: 2,
+            ..Statistics::default()
+        };
+        assert_eq!(stats.checks, 11);
+        assert_eq!(stats.revisions, 2);
+        assert_eq!(stats.max_depth, 6);
+        assert_eq!(stats.restarts, 7);
+    }
+}
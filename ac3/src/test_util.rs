@@ -0,0 +1,103 @@
+//! Fixtures shared by the tests of several modules.
+
+use std::collections::HashMap;
+
+use rand::rngs::SmallRng;
+use rand_seeder::Seeder;
+
+use crate::ac3::{Constraint, ConstraintProvider, DomainType, IdentifierType, ac3, new_constraint};
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Variables, arcs and constraints ready to search.
+pub type Fixture<D, K> = (
+    VariableProvider<D, K>,
+    Vec<(VariableID, VariableID)>,
+    HashMap<(VariableID, VariableID), Constraint<D>>,
+);
+
+pub fn simple_rng(seed_str: &str) -> SmallRng {
+    Seeder::from(seed_str).into_rng()
+}
+
+/// The values `id` can still take.
+pub fn domain<D, K>(variables: &VariableProvider<D, K>, id: K) -> Vec<D>
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    variables.find_var(id).unwrap().possible_values().clone()
+}
+
+/// The value `id` was decided on, or the first it can still take.
+pub fn value<D, K>(variables: &VariableProvider<D, K>, id: K) -> D
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    variables.find_var(id).unwrap().possible_values()[0]
+}
+
+/// Revise `a` against `b` with `constraint`, making sure the result matches checking every
+/// pair of values.
+pub fn revise<C>(constraint: &C, a: &[u32], b: &[u32]) -> Vec<u32>
+where
+    C: ConstraintProvider<u32, char>,
+{
+    let mut variables = VariableProvider::from([('a', a.to_vec()), ('b', b.to_vec())]);
+    let [a_id, b_id] = ['a', 'b'].map(|id| variables.find_id(id).unwrap());
+    let expected = {
+        let a_var = variables.get_var(a_id).unwrap();
+        let b_var = variables.get_var(b_id).unwrap();
+        a.iter()
+            .filter(|x| b.iter().any(|y| constraint.check(a_var, x, b_var, y)))
+            .copied()
+            .collect::<Vec<_>>()
+    };
+
+    ac3(&mut variables, &[(a_id, b_id)], constraint);
+    let revised = variables.get_var(a_id).unwrap().possible_values().clone();
+    assert_eq!(revised, expected);
+    revised
+}
+
+/// Variables `ids` that must all take different values from `values`.
+pub fn all_different<D, K>(ids: &[K], values: &[D]) -> Fixture<D, K>
+where
+    D: DomainType,
+    K: IdentifierType,
+{
+    let mut variables = VariableProvider::default();
+    let ids = ids
+        .iter()
+        .map(|id| variables.add_var(*id, values.to_vec()).unwrap())
+        .collect::<Vec<_>>();
+    let mut arcs = vec![];
+    let mut constraints = HashMap::new();
+    for &x in &ids {
+        for &y in &ids {
+            if x != y {
+                arcs.push((x, y));
+                constraints.insert((x, y), new_constraint(|a: &D, b: &D| a != b));
+            }
+        }
+    }
+    (variables, arcs, constraints)
+}
+
+/// a < b < c, over 1..=4.
+pub fn ordered() -> Fixture<i32, char> {
+    let variables = VariableProvider::from([
+        ('a', vec![1, 2, 3, 4]),
+        ('b', vec![1, 2, 3, 4]),
+        ('c', vec![1, 2, 3, 4]),
+    ]);
+    let [a, b, c] = ['a', 'b', 'c'].map(|id| variables.find_id(id).unwrap());
+    let arcs = vec![(a, b), (b, a), (b, c), (c, b)];
+    let constraints = HashMap::from([
+        ((a, b), new_constraint(|x: &i32, y: &i32| x < y)),
+        ((b, a), new_constraint(|x: &i32, y: &i32| x > y)),
+        ((b, c), new_constraint(|x: &i32, y: &i32| x < y)),
+        ((c, b), new_constraint(|x: &i32, y: &i32| x > y)),
+    ]);
+    (variables, arcs, constraints)
+}
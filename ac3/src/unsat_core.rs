@@ -0,0 +1,165 @@
+use std::collections::HashSet;
+
+use rand::Rng;
+
+use crate::ac3::{ConstraintProvider, DomainType, IdentifierType};
+use crate::assumptions::{Assumption, shrink};
+use crate::backtrack::{SearchOptions, Solution, search};
+use crate::statistics::Statistics;
+use crate::variable_provider::{VariableID, VariableProvider};
+
+/// Result of [`unsat_core`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Core<D> {
+    /// Outcome of searching with every pin and arc in place.
+    pub solution: Solution,
+    /// On [`Solution::NoSolution`], the pins that are part of the core.
+    pub pins: Vec<Assumption<D>>,
+    /// On [`Solution::NoSolution`], the arcs that are part of the core. Both directions of a
+    /// constraint are kept or dropped together.
+    pub arcs: Vec<(VariableID, VariableID)>,
+    /// Summed over the search with everything in place, plus one search per pin and per
+    /// constraint tried while shrinking the core.
+    pub statistics: Statistics,
+}
+
+/// Explain why a problem has no solution by finding a small set of `pins` and arcs that already
+/// has none on its own. `variables` holds the domains before pinning, e.g. every tile, while
+/// `pins` are the placements made on top of them, e.g. the cells of a pre-seeded region.
+///
+/// Pins and then constraints are dropped one at a time, keeping each drop that still leaves no
+/// solution, so the core is minimal: dropping anything else from it allows a solution. This takes
+/// a search per pin and constraint. If one of those searches stops early at a limit, the pin or
+/// constraint is kept, so the core may be larger than necessary then.
+#[must_use]
+pub fn unsat_core<K, D, CP, R>(
+    variables: &VariableProvider<D, K>,
+    arcs: &[(VariableID, VariableID)],
+    constraints: &CP,
+    rng: &mut R,
+    pins: &[Assumption<D>],
+    options: &SearchOptions,
+) -> Core<D>
+where
+    K: IdentifierType,
+    D: DomainType,
+    CP: ConstraintProvider<D, K>,
+    R: Rng + ?Sized,
+{
+    let mut statistics = Statistics::default();
+    let mut solve = |pins: &[Assumption<D>], arcs: &[(VariableID, VariableID)]| {
+        let mut pinned = variables.clone();
+        for pin in pins {
+            pin.apply(&pinned);
+        }
+        let (solution, search_statistics) = search(&mut pinned, arcs, constraints, rng, options);
+        statistics += search_statistics;
+        solution
+    };
+
+    let solution = solve(pins, arcs);
+    if solution != Solution::NoSolution {
+        return Core {
+            solution,
+            pins: vec![],
+            arcs: vec![],
+            statistics,
+        };
+    }
+
+    let core_pins = shrink(pins.to_vec(), |without| {
+        solve(without, arcs) == Solution::NoSolution
+    });
+
+    // Each constraint once, by the first direction of it in `arcs`.
+    let mut seen = HashSet::new();
+    let constraints = arcs
+        .iter()
+        .copied()
+        .filter(|&(x, y)| !seen.contains(&(y, x)) && seen.insert((x, y)))
+        .collect::<Vec<_>>();
+    let arcs_of = |constraints: &[(VariableID, VariableID)]| {
+        let constraints = constraints.iter().copied().collect::<HashSet<_>>();
+        arcs.iter()
+            .copied()
+            .filter(|&(x, y)| constraints.contains(&(x, y)) || constraints.contains(&(y, x)))
+            .collect::<Vec<_>>()
+    };
+    let core_constraints = shrink(constraints, |without| {
+        solve(&core_pins, &arcs_of(without)) == Solution::NoSolution
+    });
+
+    Core {
+        solution,
+        pins: core_pins,
+        arcs: arcs_of(&core_constraints),
+        statistics,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use crate::{
+        ac3::new_constraint,
+        assumptions::Assumption,
+        backtrack::{SearchOptions, Solution},
+        test_util::simple_rng,
+        variable_provider::VariableProvider,
+    };
+
+    use super::unsat_core;
+
+    #[test]
+    fn unsat_core_finds_the_conflicting_pins() {
+        let mut rng = simple_rng("core");
+        let variables = VariableProvider::from([
+            ('a', vec![1, 2, 3]),
+            ('b', vec![1, 2, 3]),
+            ('c', vec![1, 2, 3]),
+            ('d', vec![1, 2, 3]),
+        ]);
+        let [a, b, c, d] = ['a', 'b', 'c', 'd'].map(|id| variables.find_id(id).unwrap());
+        // a, b and c differ, d equals c.
+        let mut arcs = vec![];
+        let mut constraints = HashMap::new();
+        for (x, y) in [(a, b), (b, c), (a, c)] {
+            for arc in [(x, y), (y, x)] {
+                arcs.push(arc);
+                constraints.insert(arc, new_constraint(|p: &i32, q: &i32| p != q));
+            }
+        }
+        for arc in [(c, d), (d, c)] {
+            arcs.push(arc);
+            constraints.insert(arc, new_constraint(|p: &i32, q: &i32| p == q));
+        }
+        let options = SearchOptions::default();
+
+        let core = unsat_core(
+            &variables,
+            &arcs,
+            &constraints,
+            &mut rng,
+            &[Assumption::Assign(c, 3), Assumption::Assign(d, 3)],
+            &options,
+        );
+        assert_eq!(core.solution, Solution::Consistent);
+        assert!(core.pins.is_empty());
+
+        let pins = [
+            Assumption::Assign(a, 1),
+            Assumption::Assign(b, 2),
+            Assumption::Exclude(b, 3),
+            Assumption::Assign(d, 1),
+        ];
+        let core = unsat_core(&variables, &arcs, &constraints, &mut rng, &pins, &options);
+        assert_eq!(core.solution, Solution::NoSolution);
+        // d = 1 forces c = 1, which a = 1 already took.
+        assert_eq!(
+            core.pins,
+            vec![Assumption::Assign(a, 1), Assumption::Assign(d, 1)]
+        );
+        assert_eq!(core.arcs, vec![(a, c), (c, a), (c, d), (d, c)]);
+    }
+}
@@ -8,8 +8,13 @@ use std::{
     hash::Hash,
 };
 
+/// Stable handle to a [`Variable`]. IDs of removed variables are never handed out again, so
+/// looking one up after removal finds nothing rather than some other variable.
 #[derive(Clone, Copy, PartialEq, Hash, Eq, Debug)]
-pub struct VariableID(usize);
+pub struct VariableID {
+    slot: usize,
+    generation: u32,
+}
 
 /// A [`Variable`] is a node in the graph with a set of possible values. In a tile map it might be a
 /// single tile, for example.
@@ -28,11 +33,11 @@ where
     D: Copy,
 {
     /// Construct A variable from a set of possible values.
-    fn new(id: K, index: usize, values: Vec<D>) -> Self {
+    fn new(id: K, index: VariableID, values: Vec<D>) -> Self {
         Self {
             domain: RefCell::new(values),
             identifier: id,
-            index: VariableID(index),
+            index,
         }
     }
 
@@ -73,13 +78,17 @@ where
 /// well as their possible values (subset of the full-domain, type D).
 #[derive(Clone, Debug)]
 pub struct VariableProvider<D, K> {
-    identifiers: Vec<Variable<D, K>>,
+    /// Variables by [`VariableID`] slot, `None` once removed.
+    identifiers: Vec<Option<Variable<D, K>>>,
+    /// IDs of removed variables, whose slots can be reused under a new generation.
+    free: Vec<VariableID>,
 }
 
 impl<D, K> Default for VariableProvider<D, K> {
     fn default() -> Self {
         Self {
             identifiers: Vec::new(),
+            free: Vec::new(),
         }
     }
 }
@@ -97,8 +106,18 @@ where
             identifiers: items
                 .into_iter()
                 .enumerate()
-                .map(|(index, (id, values))| Variable::new(id, index, values))
+                .map(|(slot, (id, values))| {
+                    Some(Variable::new(
+                        id,
+                        VariableID {
+                            slot,
+                            generation: 0,
+                        },
+                        values,
+                    ))
+                })
                 .collect(),
+            free: Vec::new(),
         }
     }
 }
@@ -112,48 +131,69 @@ where
     ///
     /// Fails if the identifier is already in use.
     pub fn add_var(&mut self, id: K, values: Vec<D>) -> Result<VariableID, String> {
-        if self
-            .identifiers
-            .iter()
-            .any(|existing| existing.identifier == id)
-        {
+        if self.iter().any(|existing| existing.identifier == id) {
             Err(format!("Identifier already in use {id}"))
+        } else if let Some(removed) = self.free.pop() {
+            let var_id = VariableID {
+                slot: removed.slot,
+                generation: removed.generation + 1,
+            };
+            self.identifiers[var_id.slot] = Some(Variable::new(id, var_id, values));
+            Ok(var_id)
         } else {
-            let var = Variable::new(id, self.identifiers.len(), values);
-            let var_id = var.index;
-            self.identifiers.push(var);
+            let var_id = VariableID {
+                slot: self.identifiers.len(),
+                generation: 0,
+            };
+            self.identifiers
+                .push(Some(Variable::new(id, var_id, values)));
             Ok(var_id)
         }
     }
 
+    /// Removes a variable, returning it if it was still present. Its [`VariableID`] stays
+    /// invalid from then on, even if another variable is added later.
+    pub fn remove_var(&mut self, id: VariableID) -> Option<Variable<D, K>> {
+        self.get_var(id)?;
+        // A slot out of generations is retired rather than wrapping around to old IDs.
+        if id.generation < u32::MAX {
+            self.free.push(id);
+        }
+        self.identifiers[id.slot].take()
+    }
+
     /// Replaces the possible values for the given identifier.
     pub fn update_var(&mut self, id: VariableID, values: Vec<D>) {
-        if let Some(var) = self.identifiers.get_mut(id.0) {
+        if let Some(var) = self.get_var(id) {
             var.replace_possible_values(values);
         }
     }
 
     #[must_use]
     pub fn get_var(&self, index: VariableID) -> Option<&Variable<D, K>> {
-        self.identifiers.get(index.0)
+        self.identifiers
+            .get(index.slot)?
+            .as_ref()
+            .filter(|var| var.index == index)
     }
 
     #[must_use]
     pub fn find_var(&self, var: K) -> Option<&Variable<D, K>> {
-        self.identifiers.iter().find(|id| id.identifier == var)
+        self.iter().find(|id| id.identifier == var)
     }
 
     #[must_use]
     pub fn find_id(&self, var: K) -> Option<VariableID> {
-        self.identifiers
-            .iter()
-            .find(|id| id.identifier == var)
-            .map(|id| id.index)
+        self.find_var(var).map(|id| id.index)
+    }
+
+    /// Iterate every variable.
+    pub fn iter(&self) -> impl Iterator<Item = &Variable<D, K>> {
+        self.identifiers.iter().flatten()
     }
 
     pub(crate) fn next_reducable_variable(&mut self) -> Option<VariableID> {
-        self.identifiers
-            .iter()
+        self.iter()
             .filter_map(|v| {
                 let len = v.possible_values().len();
                 if len > 1 { Some((len, v)) } else { None }
@@ -163,6 +203,54 @@ where
     }
 
     pub(crate) fn is_consistent(&self) -> bool {
-        self.identifiers.iter().all(Variable::is_consistent)
+        self.iter().all(Variable::is_consistent)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Variable, VariableID, VariableProvider};
+
+    #[test]
+    fn removed_ids_stay_invalid() {
+        let mut variables = VariableProvider::from([('a', vec![1, 2]), ('b', vec![3])]);
+        let a = variables.find_id('a').unwrap();
+        let b = variables.find_id('b').unwrap();
+
+        let removed = variables.remove_var(a).unwrap();
+        assert_eq!(removed.identifier, 'a');
+        assert!(variables.get_var(a).is_none());
+        assert!(variables.remove_var(a).is_none());
+        assert_eq!(variables.iter().count(), 1);
+
+        // The slot is reused, but not the ID.
+        let c = variables.add_var('c', vec![4]).unwrap();
+        assert_ne!(a, c);
+        assert!(variables.get_var(a).is_none());
+        variables.update_var(a, vec![5]);
+        assert_eq!(*variables.get_var(c).unwrap().possible_values(), vec![4]);
+        assert_eq!(*variables.get_var(b).unwrap().possible_values(), vec![3]);
+
+        // Removed identifiers can be added again.
+        let a = variables.add_var('a', vec![1]).unwrap();
+        assert_eq!(variables.find_id('a'), Some(a));
+    }
+
+    #[test]
+    fn exhausted_slots_are_retired() {
+        let mut variables = VariableProvider::default();
+        let a = variables.add_var('a', vec![1]).unwrap();
+        // Pretend the slot was reused until its generations ran out.
+        let last = VariableID {
+            slot: a.slot,
+            generation: u32::MAX,
+        };
+        variables.identifiers[a.slot] = Some(Variable::new('a', last, vec![1]));
+
+        variables.remove_var(last).unwrap();
+        let b = variables.add_var('b', vec![2]).unwrap();
+        assert_ne!(b.slot, a.slot);
+        assert!(variables.get_var(last).is_none());
+        assert!(variables.get_var(a).is_none());
     }
 }